@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::exception::Exception;
+use crate::expr::Expr;
+use crate::expr::Expr::{Bool, Float, Int, List, Map, Nil, Str};
+use crate::types::Type;
+
+// conversions between Rust values and expressions, used by embedding applications
+
+impl From<i64> for Expr {
+    fn from(value: i64) -> Self { Int(value) }
+}
+
+impl From<i32> for Expr {
+    fn from(value: i32) -> Self { Int(value as i64) }
+}
+
+impl From<f64> for Expr {
+    fn from(value: f64) -> Self { Float(value) }
+}
+
+impl From<bool> for Expr {
+    fn from(value: bool) -> Self { Bool(value) }
+}
+
+impl From<&str> for Expr {
+    fn from(value: &str) -> Self { Str(value.to_owned()) }
+}
+
+impl From<String> for Expr {
+    fn from(value: String) -> Self { Str(value) }
+}
+
+impl From<()> for Expr {
+    fn from(_: ()) -> Self { Nil }
+}
+
+impl<T: Into<Expr>> From<Option<T>> for Expr {
+    fn from(value: Option<T>) -> Self { value.map(|v| v.into()).unwrap_or(Nil) }
+}
+
+impl<T: Into<Expr>> From<Vec<T>> for Expr {
+    fn from(value: Vec<T>) -> Self {
+        let values: Vec<Expr> = value.into_iter().map(|v| v.into()).collect();
+        List(Type::infer_list(&values), values)
+    }
+}
+
+impl<K: Into<Expr>, V: Into<Expr>> From<HashMap<K, V>> for Expr {
+    fn from(value: HashMap<K, V>) -> Self {
+        let pairs: Vec<(Expr, Expr)> = value.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        Map(Type::infer_map(&pairs), pairs)
+    }
+}
+
+impl TryFrom<Expr> for i64 {
+    type Error = Exception;
+    fn try_from(value: Expr) -> Result<Self, Self::Error> {
+        match value {
+            Int(x) => Ok(x),
+            _ => Err(Exception::NotA(Type::Int.name(), value.print())),
+        }
+    }
+}
+
+impl TryFrom<Expr> for f64 {
+    type Error = Exception;
+    fn try_from(value: Expr) -> Result<Self, Self::Error> {
+        match value {
+            Float(x) => Ok(x),
+            Int(x) => Ok(x as f64),
+            _ => Err(Exception::NotA(Type::Float.name(), value.print())),
+        }
+    }
+}
+
+impl TryFrom<Expr> for bool {
+    type Error = Exception;
+    fn try_from(value: Expr) -> Result<Self, Self::Error> {
+        value.to_bool()
+    }
+}
+
+impl TryFrom<Expr> for String {
+    type Error = Exception;
+    fn try_from(value: Expr) -> Result<Self, Self::Error> {
+        value.to_str().map(|s| s.to_owned())
+    }
+}
+
+impl<T: TryFrom<Expr, Error=Exception>> TryFrom<Expr> for Vec<T> {
+    type Error = Exception;
+    fn try_from(value: Expr) -> Result<Self, Self::Error> {
+        match value {
+            List(_, vec) => vec.into_iter().map(T::try_from).collect(),
+            _ => Err(Exception::NotA("List".to_owned(), value.print())),
+        }
+    }
+}
+
+impl<V: TryFrom<Expr, Error=Exception>> TryFrom<Expr> for HashMap<String, V> {
+    type Error = Exception;
+    fn try_from(value: Expr) -> Result<Self, Self::Error> {
+        match value {
+            Map(_, vec) => vec.into_iter().map(|(k, v)| Ok((String::try_from(k)?, V::try_from(v)?))).collect(),
+            _ => Err(Exception::NotA("Map".to_owned(), value.print())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(Int(2), Expr::from(2));
+        assert_eq!(Nil, Expr::from(None::<bool>));
+        assert_eq!("[1.5,2.0]", Expr::from(vec!(1.5, 2.0)).print());
+        assert_eq!("{\"a\":true}", Expr::from(HashMap::from([("a", true)])).print());
+        assert_eq!(Ok(vec!(1, 2)), Vec::<i64>::try_from(Expr::from(vec!(1, 2))));
+        assert_eq!(Ok(HashMap::from([("a".to_owned(), 1.0)])), HashMap::<String, f64>::try_from(Expr::from(HashMap::from([("a", 1)]))));
+        assert_eq!(Err(Exception::NotA("Int".to_owned(), "\"a\"".to_owned())), i64::try_from(Expr::from("a")));
+    }
+}
@@ -1,17 +1,25 @@
 use strum_macros::Display;
 use crate::utils::Resources;
 
-use self::Exception::{CannotParse, UndefinedFunction, UndefinedMethod, UndefinedSymbol, NotDefined, NotMutable, UnexpectedType, CannotInferType, CannotCastType, AlreadyDefined, NotA, UnexpectedArgumentType, WrongArgumentsNumber};
+use self::Exception::{AssertionFailed, NotEqual, InvalidJson, FileNotFound, PermissionDenied, FileAccessDisabled, FileError, CannotParse, UndefinedFunction, UndefinedMethod, UndefinedSymbol, NotDefined, NotMutable, UnexpectedType, CannotInferType, CannotCastType, AlreadyDefined, NotA, UnexpectedArgumentType, WrongArgumentsNumber, NoMatchingOverload, AmbiguousCall, NoMatchingCase, NotImplemented, CannotCompare, CannotConvert, UnknownRoundingMode, UndefinedField, DuplicateBinding, AmbiguousVariant};
 
 #[derive(Debug, Clone, PartialEq, Display)]
 pub enum Exception {
     CannotParse(String),
     DivisionByZero,
     IOError,
+    FileNotFound(String),
+    PermissionDenied(String),
+    FileAccessDisabled(String),
+    FileError(String, String),
+    InvalidJson(String, String),
+    AssertionFailed(String),
+    NotEqual(String, String),
     UndefinedSymbol(String),
     UndefinedFunction(String),
     UndefinedMethod(String),
     InfiniteLoop,
+    Interrupted,
     NotA(String, String),
     NotMutable(String),
     UnexpectedType(String),
@@ -20,7 +28,25 @@ pub enum Exception {
     AlreadyDefined(String),
     NotDefined(String),
     WrongArgumentsNumber(String, String ,String),
-    UnexpectedArgumentType(String, String)
+    UnexpectedArgumentType(String, String),
+    // the name of the function and its candidate signatures
+    NoMatchingOverload(String, String),
+    AmbiguousCall(String, String),
+    // the value for which no case of a match applies
+    NoMatchingCase(String),
+    // a variable bound twice by the same pattern
+    DuplicateBinding(String),
+    // the structure and the method of an interface it does not define
+    NotImplemented(String, String),
+    // the types of two values that have no ordering
+    CannotCompare(String, String),
+    // the value and the type it cannot be converted to
+    CannotConvert(String, String),
+    UnknownRoundingMode(String),
+    // the name after a '.' and the type of the value which has no such field
+    UndefinedField(String, String),
+    // a variant without its enum and the enums declaring it
+    AmbiguousVariant(String, String),
 }
 
 impl Exception {
@@ -32,8 +58,12 @@ impl Exception {
 
     pub fn format(&self, resources: &Resources) -> String {
         if let Some(msg) = resources.get(self.name().as_str()) {
-            format!("{}", match self {
-                CannotParse(x) |
+            match self {
+                CannotParse(x) => msg.replace("{1}", &resources.get_syntax(x)),
+                AssertionFailed(x) |
+                FileNotFound(x) |
+                PermissionDenied(x) |
+                FileAccessDisabled(x) |
                 UndefinedSymbol(x) |
                 UndefinedFunction(x) |
                 UndefinedMethod(x) |
@@ -42,12 +72,25 @@ impl Exception {
                 UnexpectedType(x) |
                 CannotInferType(x) |
                 CannotCastType(x) |
-                AlreadyDefined(x) => msg.replace("{1}",x),
+                AlreadyDefined(x) |
+                NoMatchingCase(x) |
+                DuplicateBinding(x) |
+                UnknownRoundingMode(x) => msg.replace("{1}",x),
                 NotA(x, y) |
-                UnexpectedArgumentType(x, y) => msg.replace("{1}",x).replace("{2}",y),
+                FileError(x, y) |
+                InvalidJson(x, y) |
+                NotEqual(x, y) |
+                UnexpectedArgumentType(x, y) |
+                NoMatchingOverload(x, y) |
+                AmbiguousCall(x, y) |
+                NotImplemented(x, y) |
+                CannotCompare(x, y) |
+                CannotConvert(x, y) |
+                UndefinedField(x, y) |
+                AmbiguousVariant(x, y) => msg.replace("{1}",x).replace("{2}",y),
                 WrongArgumentsNumber(x, y, z) => msg.replace("{1}",x).replace("{2}",y).replace("{3}",z),
                 _ => msg.to_string(),
-            })
+            }
         } else {
             self.name()
         }
@@ -65,5 +108,8 @@ mod tests {
 
         let resources = Resources::init("FR");
         assert_eq!("Le symbole 'a' n'est pas défini", UndefinedSymbol("a".to_owned()).format(&resources));
+        assert_eq!("'&&' n'accepte pas une valeur de type Int", UnexpectedArgumentType("&&".to_owned(), "Int".to_owned()).format(&resources));
+        let resources = Resources::init("EN");
+        assert_eq!("Unknown rounding mode \"up\", use \"trunc\", \"floor\", \"ceil\" or \"round\"", UnknownRoundingMode("\"up\"".to_owned()).format(&resources));
     }
 }
@@ -1,15 +1,20 @@
 use std::cmp::PartialEq;
+use std::collections::HashMap;
+use std::slice;
 use strum_macros::Display;
 
 use crate::exception::Exception;
+use crate::functions;
 use crate::functions::Function;
 use crate::functions::Function::BuiltIn;
 use crate::if_else;
-use crate::parser::parse;
+use crate::interrupt;
+use crate::parser::{parse, OPERATOR_METHODS};
+use crate::patterns;
 use crate::scope::Scope;
 use crate::types::Type;
 
-use self::Expr::{Block, Bool, Call, Failure, Float, Fun, Int, List, Map, Nil, Params, Str, Symbol, TypeOf};
+use self::Expr::{Block, Bool, Call, Failure, Float, Fun, Instance, Int, List, Map, Nil, Params, Str, Symbol, TypeOf, Variant};
 
 #[derive(Debug, Clone, PartialEq, Display)]
 pub enum Expr {
@@ -26,11 +31,22 @@ pub enum Expr {
     Fun(String, Type, Function),
     List(Type, Vec<Expr>),
     Map(Type, Vec<(Expr, Expr)>),
-    Struct(String, Vec<(String, Type)>),
+    // name, type parameters, fields and implemented interfaces of a structure
+    Struct(String, Vec<Type>, Vec<(String, Type)>, Vec<String>),
+    Instance(Type, Vec<(String, Expr)>),
+    // name and variants of an enum, with the fields of each variant
+    Enum(String, Vec<(String, Vec<(String, Type)>)>),
+    // name of an interface and the signatures of its methods, without their receiver
+    Interface(String, Vec<(String, Type)>),
+    // a value of an enum: its type, the name of the variant and its fields
+    Variant(Type, String, Vec<(String, Expr)>),
     Params(Vec<(String, Type)>),
 }
 
 
+// the names and types of the fields of a variant
+type Fields = Vec<(String, Type)>;
+
 pub const TRUE: Expr = Bool(true);
 pub const FALSE: Expr = Bool(false);
 pub const NIL: Expr = Nil;
@@ -51,6 +67,15 @@ impl Expr {
     pub fn is_fun(&self) -> bool {
         matches!(self, Fun(_, _, _))
     }
+    // a collection is constant when it does not contain symbols or calls to evaluate
+    pub fn is_constant(&self) -> bool {
+        match self {
+            Symbol(_) | Call(_, _) | Block(_) => false,
+            List(_, vec) => vec.iter().all(Expr::is_constant),
+            Map(_, vec) => vec.iter().all(|(k, v)| k.is_constant() && v.is_constant()),
+            _ => true,
+        }
+    }
 
     pub fn to_exception(&self) -> &Exception {
         match self { Failure(ex) => ex, _ => panic!("not a failure") }
@@ -58,13 +83,14 @@ impl Expr {
 
     pub fn get_type(&self) -> &Type {
         match self {
-            Nil => &Type::Any,
+            Nil | Failure(_) => &Type::Any,
             Bool(_) => &Type::Bool,
             Int(_) => &Type::Int,
             Float(_) => &Type::Float,
             Str(_) => &Type::Str,
             List(t, _) => t,
             Map(t, _) => t,
+            Instance(t, _) | Variant(t, _, _) => t,
             _ => panic!("unknown type {:?}", self)
         }
     }
@@ -103,8 +129,19 @@ impl Expr {
     pub fn eval(&self, scope: &Scope) -> Result<Expr, Exception> {
         match self {
             Failure(e) => Err(e.clone()),
-            Nil | Int(_) | Float(_) | Str(_) | Bool(_)  | List(_,_ )  | Map(_, _)  => Ok(self.clone()),
+            List(_, vec) if !self.is_constant() => {
+                let values = vec.iter().map(|e| e.eval(scope)).collect::<Result<Vec<_>, _>>()?;
+                Ok(List(Type::infer_list(&values), values))
+            }
+            Map(_, vec) if !self.is_constant() => {
+                let pairs = vec.iter().map(|(k, v)| Ok((k.eval(scope)?, v.eval(scope)?))).collect::<Result<Vec<_>, Exception>>()?;
+                Ok(Map(Type::infer_map(&pairs), pairs))
+            }
+            Nil | Int(_) | Float(_) | Str(_) | Bool(_)  | List(_,_ )  | Map(_, _) | Instance(_, _) | Variant(_, _, _) => Ok(self.clone()),
             Symbol(name) => handle_symbol(name, scope),
+            Call(name, args) if name == "match" => patterns::run_match(args, scope),
+            Call(name, args) if name == "and" || name == "or" => functions::short_circuit(name, args, scope),
+            Call(name, args) if name == "." => handle_field(&args[0], args[1].to_symbol()?, scope),
             Call(name, args) => handle_call(name, args, scope),
             _ => panic!("not implemented {:?}", self),
         }
@@ -117,31 +154,59 @@ impl Expr {
             _ => self.eval(scope)
         }
     }
+    // type of the value of an expression, inferred without evaluating it; the type of a declaration is the declared one
+    pub fn infer_type(&self, scope: &Scope) -> Result<Type, Exception> {
+        match self {
+            Failure(e) => Err(e.clone()),
+            List(_, vec) if !self.is_constant() => Ok(Type::List(Box::new(infer_common(vec.iter(), scope)?))),
+            Map(_, vec) if !self.is_constant() => Ok(Type::Map(Box::new(infer_common(vec.iter().map(|p| &p.0), scope)?),
+                                                                Box::new(infer_common(vec.iter().map(|p| &p.1), scope)?))),
+            Nil | Int(_) | Float(_) | Str(_) | Bool(_) | List(_, _) | Map(_, _) | Instance(_, _) | Variant(_, _, _) => Ok(self.get_type().clone()),
+            Symbol(name) => handle_symbol(name, scope).map(|v| v.value_type()),
+            Block(body) => body.last().map(|e| e.infer_type(scope)).unwrap_or(Ok(Type::Any)),
+            Call(name, args) => infer_call(name, args, scope),
+            _ => Ok(self.value_type()),
+        }
+    }
+    // functions and structures have the type of their signature or constructor
+    pub fn value_type(&self) -> Type {
+        match self {
+            Fun(_, t, _) => t.clone(),
+            Expr::Struct(name, params, fields, _) => Type::Fun(fields.iter().map(|f| f.1.clone()).collect(), Box::new(Type::Struct(name.to_owned(), params.clone()))),
+            Symbol(_) | TypeOf(_) | Block(_) | Call(_, _) | Params(_) | Expr::Enum(..) | Expr::Interface(..) => Type::Any,
+            _ => self.get_type().clone(),
+        }
+    }
     pub fn eval_or_failed(&self, scope: &mut Scope) -> Expr {
         match self {
             Failure(_) => self.clone(),
-            expr => expr.eval_mutable(scope).unwrap_or_else(|ex| Failure(ex))
+            expr => expr.eval_mutable(scope).unwrap_or_else(Failure)
         }
     }
-    pub fn expect(self, expected: &Type) -> Result<Expr, Exception> {
-        let value_type = self.get_type();
-        if expected.is_defined() {
-            if value_type.is_defined() {
-                if !value_type.matches(expected) {
-                    return Err(Exception::UnexpectedType(value_type.print()));
-                }
-            } else {
-                // soft cast
-                return match self {
-                    List(_, vec) => Ok(List(expected.clone(), vec.clone())),
-                    Map(_, vec) => Ok(Map(expected.clone(), vec.clone())),
-                    _ => Err(Exception::CannotCastType(expected.print())),
-                }
-            }
-        } else if !value_type.is_defined() {
-            return Err(Exception::CannotInferType(value_type.print()));
+    pub fn expect(self, expected: &Type, scope: &Scope) -> Result<Expr, Exception> {
+        if let Failure(ex) = self {
+            // a failed Try can only be stored if its type allows it
+            return if_else!(expected.is_defined() && !expected.accepts_failure(), Err(ex), Ok(Failure(ex)))
+        }
+        let value_type = self.value_type();
+        if !expected.is_defined() {
+            return if_else!(value_type.is_defined(), Ok(self), Err(Exception::CannotInferType(value_type.print())))
+        }
+        // the type of a collection is the expected one when all its elements have the expected type, so that
+        // [1, 2] is a List<Number> and [1, nil] a List<Int?>
+        let collection_type = expected.without_option();
+        let cast = match (&self, collection_type) {
+            (List(_, vec), Type::List(t)) => vec.iter().map(|e| e.clone().expect(t, scope)).collect::<Result<Vec<_>, _>>()
+                .map(|vec| List(collection_type.clone(), vec)).ok(),
+            (Map(_, vec), Type::Map(k, v)) => vec.iter().map(|(a, b)| Ok((a.clone().expect(k, scope)?, b.clone().expect(v, scope)?))).collect::<Result<Vec<_>, Exception>>()
+                .map(|vec| Map(collection_type.clone(), vec)).ok(),
+            _ => None,
+        };
+        match cast {
+            Some(value) => Ok(value),
+            None if value_type.is_subtype_of(expected, scope) => Ok(self),
+            None => Err(Exception::UnexpectedType(value_type.print())),
         }
-        Ok(self)
     }
 
 
@@ -165,6 +230,9 @@ impl Expr {
             Params(vec) => print_vec(vec, ",", "(", ")", |p| format!("{}:{}", p.0, p.1)),
             Map(_, vec) => print_vec(vec, ",", "{", "}", |p| format!("{}:{}", p.0.print(), p.1.print())),
             List(_, vec) => print_vec(vec, ",", "[", "]", Expr::print),
+            Instance(Type::Struct(name, _), vec) => print_vec(vec, ",", &(name.to_owned() + "("), ")", |p| format!("{}={}", p.0, p.1.print())),
+            Variant(_, name, vec) if vec.is_empty() => name.to_owned(),
+            Variant(_, name, vec) => print_vec(vec, ",", &(name.to_owned() + "("), ")", |p| format!("{}={}", p.0, p.1.print())),
             Block(vec) => print_vec(vec, ";", "{", "}", Expr::print),
             Call(name, vec) => print_vec(vec, ",", &(name.to_owned() + "("), ")",  Expr::print),
             _ => self.name()
@@ -181,26 +249,227 @@ fn print_float(x: &f64) -> String  {
     if_else!(str.contains('.'), str, format!("{}.0", str))
 }
 
+fn infer_common<'a>(exprs: impl Iterator<Item = &'a Expr>, scope: &Scope) -> Result<Type, Exception> {
+    Ok(Type::common(exprs.map(|e| e.infer_type(scope)).collect::<Result<Vec<_>, _>>()?.into_iter()))
+}
+
 fn handle_symbol(name: &str, scope: &Scope) -> Result<Expr, Exception> {
-    scope.get_value(name).ok_or_else(|| Exception::UndefinedSymbol(name.to_owned()))
+    scope.find(name).ok_or_else(|| undefined(name, Exception::UndefinedSymbol(name.to_owned()), scope))
 }
 
-// TODO: impl a better solution to find the eligible functions
-fn handle_call(name: &str, args: &Vec<Expr>, scope: &Scope) -> Result<Expr, Exception> {
-    match scope.find(name) {
-        Some(Fun(name, types, fun)) => apply_fun(name, types, args, fun, scope),
-        _ if args.len() == 0 => Err(Exception::UndefinedFunction(name.to_owned())),
+// an undefined name can be a variant declared by several enums, which must be qualified
+fn undefined(name: &str, ex: Exception, scope: &Scope) -> Exception {
+    let enums = scope.variant_enums(name);
+    if_else!(enums.len() > 1, Exception::AmbiguousVariant(name.to_owned(), enums.join(", ")), ex)
+}
+
+// the definition of a function or a method is selected from the types of the arguments
+fn handle_call(name: &str, args: &[Expr], scope: &Scope) -> Result<Expr, Exception> {
+    if let Some(Expr::Struct(name, params, fields, _)) = scope.find(name) {
+        return new_instance(&name, &params, &fields, args, scope)
+    }
+    if let Some((enum_name, fields, args)) = find_variant(name, args, scope) {
+        return new_variant(&enum_name, name, &fields, args, scope)
+    }
+    let values = args.iter().map(|e| e.eval(scope)).collect::<Result<Vec<Expr>, Exception>>()?;
+    let types: Vec<Type> = values.iter().map(|v| v.value_type()).collect();
+    match select(name, &candidates(name, types.first(), scope)?, &types, scope)? {
+        Fun(name, specs, fun) => apply_fun(name, specs, values, fun, scope),
+        other => Err(Exception::NotA("Fun".to_owned(), other.print())),
+    }
+}
+
+// a name after a '.' without parentheses is a field of a structure or a variant, or a variant qualified by its enum
+fn handle_field(receiver: &Expr, name: &str, scope: &Scope) -> Result<Expr, Exception> {
+    if let Some((enum_name, fields, args)) = find_variant(name, slice::from_ref(receiver), scope) {
+        return new_variant(&enum_name, name, &fields, args, scope)
+    }
+    let value = receiver.eval(scope)?;
+    match &value {
+        Instance(_, fields) | Variant(_, _, fields) => fields.iter().find(|f| f.0 == name).map(|f| f.1.clone()),
+        _ => None,
+    }.ok_or_else(|| Exception::UndefinedField(name.to_owned(), value.value_type().print()))
+}
+
+// the methods of the type of the receiver and of its super types, followed by the definitions of a function
+fn candidates(name: &str, receiver: Option<&Type>, scope: &Scope) -> Result<Vec<Expr>, Exception> {
+    let mut candidates: Vec<Expr> = receiver.map(|r| r.all_method_names(name, scope)).unwrap_or_default().iter()
+        .flat_map(|m| scope.global().find_overloads(m)).map(|f| f.1).collect();
+    if matches!(scope.find(name), Some(Fun(..))) {
+        candidates.extend(scope.find_overloads(name).into_iter().map(|f| f.1))
+    }
+    match (candidates.is_empty(), receiver) {
+        (false, _) => Ok(candidates),
+        (true, Some(_)) => Err(undefined(name, Exception::UndefinedMethod(name.to_owned()), scope)),
+        (true, None) => Err(undefined(name, Exception::UndefinedFunction(name.to_owned()), scope)),
+    }
+}
+
+fn is_method(fun: &Expr) -> bool {
+    matches!(fun, Fun(name, ..) if name.contains('.'))
+}
+
+// the most specific definition accepting the types of the arguments, the errors list the candidates
+// when none or several of them are eligible. A single definition checks its arguments when it is applied.
+// The functions take precedence over the methods of the receiver, except for the operators which cannot be redefined
+fn select<'a>(name: &str, candidates: &'a [Expr], types: &[Type], scope: &Scope) -> Result<&'a Expr, Exception> {
+    if candidates.len() == 1 {
+        return Ok(&candidates[0])
+    }
+    let mut eligible: Vec<&Expr> = candidates.iter().filter(|f| accepts(&f.value_type(), types, scope)).collect();
+    let preferred = |f: &&Expr| is_method(f) == OPERATOR_METHODS.contains(&name);
+    if eligible.iter().any(preferred) {
+        eligible.retain(preferred)
+    }
+    let best: Vec<&Expr> = eligible.iter().copied().filter(|f| eligible.iter().all(|g| is_more_specific(&f.value_type(), &g.value_type(), scope))).collect();
+    match (eligible.len(), best.len()) {
+        (0, _) => Err(Exception::NoMatchingOverload(name.to_owned(), signatures(&candidates.iter().collect::<Vec<_>>()))),
+        (_, 1) => Ok(best[0]),
+        _ => Err(Exception::AmbiguousCall(name.to_owned(), signatures(&eligible))),
+    }
+}
+
+// an argument of type Any, like nil, is eligible, its value is checked when the function is applied
+fn accepts(signature: &Type, types: &[Type], scope: &Scope) -> bool {
+    let mut bindings = HashMap::new();
+    match signature {
+        Type::Fun(inputs, _) if inputs.first() == Some(&Type::Macro) => true,
+        Type::Fun(inputs, _) => inputs.len() == types.len() && inputs.iter().zip(types).all(|(i, t)| *t == Type::Any || i.unify(t, &mut bindings, scope)),
+        _ => false,
+    }
+}
+
+// a signature is more specific than another when all its arguments are subtypes, a macro takes any arguments
+fn is_more_specific(signature: &Type, other: &Type, scope: &Scope) -> bool {
+    match (signature, other) {
+        (_, Type::Fun(others, _)) if others.first() == Some(&Type::Macro) => true,
+        (Type::Fun(inputs, _), Type::Fun(others, _)) => inputs.len() == others.len() && inputs.iter().zip(others).all(|(i, o)| i.is_subtype_of(o, scope)),
+        _ => false,
+    }
+}
+
+fn signatures(candidates: &[&Expr]) -> String {
+    candidates.iter().map(|f| match f {
+        Fun(name, types, _) => format!("{}{}", name, types.print()),
+        _ => f.print(),
+    }).collect::<Vec<_>>().join(", ")
+}
+
+fn infer_call(name: &str, args: &[Expr], scope: &Scope) -> Result<Type, Exception> {
+    match name {
+        "val" | "var" | "const" => match &args[1] {
+            TypeOf(t) => Ok(t.clone()),
+            _ => args[2].infer_type(scope),
+        },
+        "assign" => args[1].infer_type(scope),
+        "and" | "or" => Ok(Type::Bool),
+        "if" => {
+            let then = args[1].infer_type(scope)?;
+            Ok(if_else!(then == args[2].infer_type(scope)?, then, Type::Any))
+        }
+        "match" => {
+            // the bodies using the variables bound by their pattern cannot be inferred
+            let types: Vec<Type> = args[1..].iter().filter_map(|c| match c {
+                Call(_, case) => case[2].infer_type(scope).ok(),
+                _ => None,
+            }).collect();
+            Ok(if_else!(types.len() == args.len() - 1 && types.windows(2).all(|w| w[0] == w[1]), types[0].clone(), Type::Any))
+        }
+        "." => {
+            let name = args[1].to_symbol()?;
+            if let Some((enum_name, _, _)) = find_variant(name, &args[..1], scope) {
+                return Ok(Type::Enum(enum_name))
+            }
+            Ok(field_type(&args[0].infer_type(scope)?, name, scope).unwrap_or(Type::Any))
+        }
+        "fun" => Ok(Type::Fun(args[1].to_params()?.iter().map(|p| p.1.clone()).collect(), Box::new(args[2].to_type()?.clone()))),
+        "struct" => Ok(Type::Fun(args[1].to_params()?.iter().map(|p| p.1.clone()).collect(), Box::new(args[2].to_type()?.clone()))),
         _ => {
-            for method in args[0].eval(scope)?.get_type().all_method_names(name) {
-                if let Some(Fun(name, types, fun)) =  scope.global().get(&method) {
-                    return apply_fun(name, types, args, fun, scope);
-                }}
-            return Err(Exception::UndefinedMethod(name.to_owned()));
+            if let Some((enum_name, _, _)) = find_variant(name, args, scope) {
+                return Ok(Type::Enum(enum_name.to_owned()))
+            }
+            let fun = match scope.find(name) {
+                Some(value @ Expr::Struct(..)) => value,
+                found => {
+                    // a function does not need the type of its first argument
+                    let receiver = match args.first() {
+                        Some(arg) if matches!(found, Some(Fun(..))) => arg.infer_type(scope).ok(),
+                        Some(arg) => Some(arg.infer_type(scope)?),
+                        None => None,
+                    };
+                    let candidates = candidates(name, receiver.as_ref(), scope)?;
+                    if_else!(candidates.len() == 1, candidates[0].clone(),
+                        select(name, &candidates, &args.iter().map(|a| a.infer_type(scope)).collect::<Result<Vec<_>, _>>()?, scope)?.clone())
+                }
+            };
+            match fun.value_type() {
+                Type::Fun(_, output) if *output == Type::Number => {
+                    // the result of Int operations is an Int
+                    let types = args.iter().map(|a| a.infer_type(scope)).collect::<Result<Vec<_>, _>>()?;
+                    Ok(if_else!(types.iter().all(|t| *t == Type::Int), Type::Int, if_else!(types.contains(&Type::Float), Type::Float, Type::Number)))
+                }
+                Type::Fun(inputs, output) if output.is_generic() => {
+                    // the type parameters are bound to the inferred types of the arguments
+                    let mut bindings = HashMap::new();
+                    for (input, arg) in inputs.iter().zip(args) {
+                        input.unify(&arg.infer_type(scope)?, &mut bindings, scope);
+                    }
+                    Ok(output.substitute(&bindings))
+                }
+                Type::Fun(_, output) => Ok(*output),
+                _ => Ok(Type::Any),
+            }
         }
     }
 }
 
+// the type of a field of a structure, with the type arguments of the structure
+fn field_type(receiver: &Type, name: &str, scope: &Scope) -> Option<Type> {
+    let Type::Struct(struct_name, args) = receiver else { return None };
+    match scope.find(struct_name)? {
+        Expr::Struct(_, params, fields, _) => {
+            let bindings: HashMap<String, Type> = params.iter().zip(args).filter_map(|(p, a)| match p {
+                Type::Param(n) => Some((n.to_owned(), a.clone())),
+                _ => None,
+            }).collect();
+            fields.iter().find(|f| f.0 == name).map(|f| f.1.substitute(&bindings))
+        }
+        _ => None,
+    }
+}
+
+// the type parameters of a generic structure are bound to the types of the values
+fn new_instance(name: &str, params: &[Type], fields: &[(String, Type)], args: &[Expr], scope: &Scope) -> Result<Expr, Exception> {
+    let values = args.iter().map(|e| e.eval(scope)).collect::<Result<Vec<Expr>, Exception>>()?;
+    let types: Vec<Type> = fields.iter().map(|f| f.1.clone()).collect();
+    let mut bindings = HashMap::new();
+    if let Some(Err(ex)) = check_arguments(name, &types, &values, &mut bindings, scope) {
+        return Err(ex)
+    }
+    Ok(Instance(Type::Struct(name.to_owned(), params.to_vec()).substitute(&bindings), fields.iter().map(|f| f.0.clone()).zip(values).collect()))
+}
 
+// the enum and the fields of a variant, with the arguments of its constructor: a variant is built with its name,
+// like Circle(1.0), or qualified by the name of its enum, like Shape.Circle(1.0) or Color.Red
+fn find_variant<'a>(name: &str, args: &'a [Expr], scope: &Scope) -> Option<(String, Fields, &'a [Expr])> {
+    let (definition, args) = match args.first() {
+        Some(Symbol(s)) if matches!(scope.find(s), Some(Expr::Enum(..))) => (scope.find(s)?, &args[1..]),
+        _ => (scope.find(name)?, args),
+    };
+    match definition {
+        Expr::Enum(enum_name, variants) => variants.into_iter().find(|v| v.0 == name).map(|v| (enum_name, v.1, args)),
+        _ => None,
+    }
+}
+
+fn new_variant(enum_name: &str, name: &str, fields: &[(String, Type)], args: &[Expr], scope: &Scope) -> Result<Expr, Exception> {
+    let values = args.iter().map(|e| e.eval(scope)).collect::<Result<Vec<Expr>, Exception>>()?;
+    let types: Vec<Type> = fields.iter().map(|f| f.1.clone()).collect();
+    if let Some(Err(ex)) = check_arguments(name, &types, &values, &mut HashMap::new(), scope) {
+        return Err(ex)
+    }
+    Ok(Variant(Type::Enum(enum_name.to_owned()), name.to_owned(), fields.iter().map(|f| f.0.clone()).zip(values).collect()))
+}
 
 fn handle_macro(scope: &mut Scope, name: &String, args: &Vec<Expr>) -> Result<Expr, Exception> {
     if let Some(Fun(_, _, BuiltIn(lambda))) = scope.global().get(name) {
@@ -211,10 +480,13 @@ fn handle_macro(scope: &mut Scope, name: &String, args: &Vec<Expr>) -> Result<Ex
 }
 
 
+// the declarations of a block are local to it, it can assign the variables of the enclosing scopes
 fn handle_block(body: &Vec<Expr>, scope: &mut Scope) -> Result<Expr, Exception> {
+    let mut local = scope.child();
     let mut result = Ok(Nil);
     for expr in body {
-        result = expr.eval_mutable(scope);
+        interrupt::check()?;
+        result = expr.eval_mutable(&mut local);
         if result.is_err() {
             break;
         }
@@ -222,34 +494,30 @@ fn handle_block(body: &Vec<Expr>, scope: &mut Scope) -> Result<Expr, Exception>
     result
 }
 
-fn apply_fun(name: &str, specs: &Type, args: &Vec<Expr>, fun: &Function, scope: &Scope) ->  Result<Expr, Exception> {
-    args.iter().map(|e| e.eval(scope)).collect::<Result<Vec<Expr>, Exception>>().and_then(|values| {
-        match specs {
-            Type::Fun(input, _output) => check_arguments(name, input, &values).or(Some(fun.apply(&values, scope))).unwrap(),
-            _ => Err(Exception::NotA("Fun".to_owned(), specs.print())),
-        }
-    })
+fn apply_fun(name: &str, specs: &Type, values: Vec<Expr>, fun: &Function, scope: &Scope) ->  Result<Expr, Exception> {
+    match specs {
+        Type::Fun(input, _output) => check_arguments(name, input, &values, &mut HashMap::new(), scope).unwrap_or_else(|| fun.apply(&values, scope)),
+        _ => Err(Exception::NotA("Fun".to_owned(), specs.print())),
+    }
 }
 
 
-fn check_arguments(name: &str, expected: &Vec<Type>, values: &Vec<Expr>) -> Option<Result<Expr, Exception>> {
-    //println!("#check_arguments({name},{expected:?} {values:?})");
-    if matches!(expected.get(0), Some(Type::Macro)) {
-        return None
-    }
-    if matches!(expected.get(0), Some(Type::List(..))) {
-        // TODO: handle collections parameters
+// the type parameters of a generic function are bound to the types of the values
+fn check_arguments(name: &str, expected: &[Type], values: &[Expr], bindings: &mut HashMap<String, Type>, scope: &Scope) -> Option<Result<Expr, Exception>> {
+    if matches!(expected.first(), Some(Type::Macro)) {
         return None
     }
     if expected.len() != values.len() {
         return Some(Err(Exception::WrongArgumentsNumber(name.to_owned(), expected.len().to_string(), values.len().to_string())))
     }
-    if matches!(expected.get(0), Some(Type::Any)) {
+    if matches!(expected.first(), Some(Type::Any)) {
         return None
     }
-    expected.iter().zip(values.iter()).find(|(e, v)| !v.get_type().matches(*e)).and_then(|p|
-        Some(Err(Exception::UnexpectedArgumentType(name.to_owned(), p.1.get_type().print())))
-    )
+    // a failed Try passed to a function which does not expect it raises its exception
+    if let Some((_, Failure(ex))) = expected.iter().zip(values.iter()).find(|(e, v)| v.is_failure() && !e.accepts_failure()) {
+        return Some(Err(ex.clone()))
+    }
+    expected.iter().zip(values.iter()).find(|(e, v)| !e.unify(&v.value_type(), bindings, scope)).map(|p| Err(Exception::UnexpectedArgumentType(name.to_owned(), p.1.value_type().print())))
 }
 
 
@@ -264,4 +532,27 @@ mod tests {
         assert_eq!("Int", expr.to_string());
         assert_eq!("Int", expr.name());
     }
+
+    #[test]
+    fn test_infer_type() {
+        let mut scope = Scope::init();
+        scope.exec("var n = 0");
+        scope.exec("fun half(x: Int): Float = x / 2.0");
+        scope.exec("struct Point(x: Float, y: Float)");
+        let infer = |str: &str| scope.read(str).infer_type(&scope).map(|t| t.print());
+        assert_eq!(Ok("Int".to_owned()), infer("n + 1"));
+        assert_eq!(Ok("Float".to_owned()), infer("half(n) * 2"));
+        assert_eq!(Ok("(Int)->Float".to_owned()), infer("half"));
+        assert_eq!(Ok("(Float,Float)->Point".to_owned()), infer("Point"));
+        assert_eq!(Ok("Point".to_owned()), infer("Point(1.0, 2.0)"));
+        assert_eq!(Ok("Str".to_owned()), infer("\" a \".trim()"));
+        assert_eq!(Ok("Str!".to_owned()), infer("readFile(\"a.txt\")"));
+        assert_eq!(Ok("Bool".to_owned()), infer("n == 1 && true"));
+        assert_eq!(Ok("List<Int>".to_owned()), infer("val l = [1, 2]"));
+        assert_eq!(Ok("Any".to_owned()), infer("if (true) 1 else \"a\""));
+        assert_eq!(Ok("Int".to_owned()), infer("n = n + 1"));
+        assert_eq!(Err(Exception::UndefinedSymbol("z".to_owned())), infer("z + 1"));
+        // nothing is evaluated
+        assert_eq!("0", scope.exec("n"));
+    }
 }
@@ -0,0 +1,100 @@
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+
+use crate::exception::Exception;
+use crate::expr::Expr;
+use crate::expr::Expr::{Bool, Failure, Int, List, Str};
+use crate::scope::Scope;
+use crate::types::Type;
+
+// All file functions return a Try value: either the result or a Failure
+
+pub fn read_file(path: &str, scope: &Scope) -> Result<Expr, Exception> {
+    try_io(path, scope, || fs::read_to_string(path).map(Str))
+}
+
+pub fn read_lines(path: &str, scope: &Scope) -> Result<Expr, Exception> {
+    try_io(path, scope, || fs::read_to_string(path).map(|s| to_list(s.lines())))
+}
+
+pub fn write_file(path: &str, content: &str, scope: &Scope) -> Result<Expr, Exception> {
+    try_io(path, scope, || fs::write(path, content).map(|_| Int(content.len() as i64)))
+}
+
+pub fn append_file(path: &str, content: &str, scope: &Scope) -> Result<Expr, Exception> {
+    try_io(path, scope, || OpenOptions::new().append(true).create(true).open(path)
+        .and_then(|mut f| f.write_all(content.as_bytes())).map(|_| Int(content.len() as i64)))
+}
+
+pub fn file_exists(path: &str, scope: &Scope) -> Result<Expr, Exception> {
+    try_io(path, scope, || fs::exists(path).map(Bool))
+}
+
+pub fn list_dir(path: &str, scope: &Scope) -> Result<Expr, Exception> {
+    try_io(path, scope, || fs::read_dir(path).and_then(|entries| {
+        let mut names = entries.map(|e| e.map(|e| e.file_name().to_string_lossy().to_string())).collect::<io::Result<Vec<_>>>()?;
+        names.sort();
+        Ok(to_list(names.iter().map(String::as_str)))
+    }))
+}
+
+pub fn delete_file(path: &str, scope: &Scope) -> Result<Expr, Exception> {
+    try_io(path, scope, || fs::remove_file(path).map(|_| Bool(true)))
+}
+
+fn try_io(path: &str, scope: &Scope, action: impl FnOnce() -> io::Result<Expr>) -> Result<Expr, Exception> {
+    if scope.is_sandboxed() {
+        return Ok(Failure(Exception::FileAccessDisabled(path.to_owned())))
+    }
+    Ok(action().unwrap_or_else(|e| Failure(to_exception(path, e))))
+}
+
+fn to_exception(path: &str, error: io::Error) -> Exception {
+    match error.kind() {
+        io::ErrorKind::NotFound => Exception::FileNotFound(path.to_owned()),
+        io::ErrorKind::PermissionDenied => Exception::PermissionDenied(path.to_owned()),
+        _ => Exception::FileError(path.to_owned(), error.to_string()),
+    }
+}
+
+fn to_list<'a>(lines: impl Iterator<Item=&'a str>) -> Expr {
+    List(Type::List(Box::new(Type::Str)), lines.map(|s| Str(s.to_owned())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn test_files() {
+        let mut scope = Scope::init();
+        let dir = env::temp_dir().to_string_lossy().to_string();
+        let path = env::temp_dir().join(format!("grolang_test_files_{}.txt", std::process::id())).to_string_lossy().to_string();
+        scope.set("dir", Str(dir), None);
+        scope.set("path", Str(path.clone()), None);
+        scope.set("text", Str("a\nbc\n".to_owned()), None);
+        assert_eq!("5", scope.exec("writeFile(path, text)"));
+        assert_eq!("3", scope.exec(r#"path.appendFile("def")"#));
+        assert_eq!("true", scope.exec("fileExists(path)"));
+        assert_eq!(r#"["a","bc","def"]"#, scope.exec("readLines(path)"));
+        assert_eq!("true", scope.exec("listDir(dir).isSuccess()"));
+        assert_eq!("true", scope.exec("deleteFile(path)"));
+        assert_eq!("false", scope.exec("fileExists(path)"));
+        assert_eq!(format!("FileNotFound({})", path), scope.exec("readFile(path)"));
+        assert_eq!("true", scope.exec("readFile(path).isFailure()"));
+        assert_eq!(r#""none""#, scope.exec(r#"readFile(path).getOrElse("none")"#));
+        assert_eq!(format!("FileNotFound({})", path), scope.exec("val s: Str = readFile(path)"));
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let mut scope = Scope::init();
+        scope.set_sandbox(true);
+        assert_eq!("FileAccessDisabled(/tmp)", scope.exec(r#"listDir("/tmp")"#));
+        assert_eq!("FileAccessDisabled(a.txt)", scope.exec(r#"writeFile("a.txt", "a")"#));
+    }
+}
@@ -0,0 +1,489 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use pest::iterators::Pair;
+use pest::Parser;
+
+use crate::if_else;
+use crate::parser::{GroParser, Rule, SyntaxError};
+use crate::utils::{language, Resources};
+
+// collections longer than that are split on several lines
+const MAX_WIDTH: usize = 100;
+const INDENT: &str = "  ";
+
+// the grammar skips the comments, so they are collected from the source text
+#[derive(Debug)]
+struct Comment {
+    line: usize,
+    pos: usize,
+    text: String,
+}
+
+struct Formatter<'a> {
+    lines: Vec<&'a str>,
+    source: &'a str,
+    comments: Vec<Comment>,
+    out: String,
+    indent: usize,
+    // line of the last statement or comment, None at the beginning of a block
+    last_line: Option<usize>,
+    // collections are never split, used to measure their width
+    flat: bool,
+}
+
+/// Formats a source text: one statement per line, blocks indented by 2 spaces and comments kept.
+pub fn format_source(source: &str) -> Result<String, SyntaxError> {
+    let pairs = GroParser::parse(Rule::Program, source).map_err(|e| SyntaxError::new(e, source))?;
+    let mut formatter = Formatter::new(source, false);
+    formatter.comments = find_comments(source);
+    formatter.statements(pairs.filter(|p| p.as_rule() != Rule::EOI).collect(), source.len());
+    if !formatter.out.is_empty() {
+        formatter.out.push('\n')
+    }
+    Ok(formatter.out)
+}
+
+/// Formats in place the .gro files, or only checks that they are formatted.
+/// Returns false if a file is not formatted in check mode, or cannot be parsed.
+pub fn format_files(paths: &[String], check: bool) -> bool {
+    let mut files = Vec::new();
+    paths.iter().for_each(|p| find_files(Path::new(p), ".gro", &mut files));
+    let resources = Resources::init(&language());
+    let mut success = true;
+    for file in files {
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(e) => { eprintln!("{}: {}", file.display(), e); success = false; continue }
+        };
+        match format_source(&source) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if check => { println!("{}", file.display()); success = false }
+            Ok(formatted) => if let Err(e) = fs::write(&file, formatted) {
+                eprintln!("{}: {}", file.display(), e);
+                success = false
+            },
+            Err(e) => { eprintln!("{}:{}:{}: {}", file.display(), e.line, e.column, e.format(&resources)); success = false }
+        }
+    }
+    success
+}
+
+// the file, or the files of the directory and its subdirectories with a name ending with the suffix
+pub(crate) fn find_files(path: &Path, suffix: &str, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path).map(|d| d.flatten().map(|e| e.path()).collect()).unwrap_or_default();
+        entries.sort();
+        entries.iter().filter(|e| e.is_dir() || e.to_string_lossy().ends_with(suffix)).for_each(|e| find_files(e, suffix, files))
+    } else {
+        files.push(path.to_path_buf())
+    }
+}
+
+fn find_comments(source: &str) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    let (mut line, mut in_string, mut escaped, mut comment_end) = (1, false, false, 0);
+    for (pos, c) in source.char_indices() {
+        match c {
+            _ if pos < comment_end => continue,
+            '\n' => line += 1,
+            '\\' if in_string => { escaped = !escaped; continue }
+            '"' if !escaped => in_string = !in_string,
+            '#' if !in_string => {
+                comment_end = source[pos..].find('\n').map(|e| pos + e).unwrap_or(source.len());
+                comments.push(Comment { line, pos, text: source[pos..comment_end].trim_end().to_owned() })
+            }
+            _ => {}
+        }
+        escaped = false
+    }
+    comments
+}
+
+impl Formatter<'_> {
+    fn new(source: &str, flat: bool) -> Formatter<'_> {
+        Formatter { lines: source.split('\n').collect(), source, comments: Vec::new(), out: String::new(), indent: 0, last_line: None, flat }
+    }
+
+    fn push(&mut self, str: &str) { self.out.push_str(str) }
+
+    fn column(&self) -> usize {
+        self.out[self.out.rfind('\n').map(|p| p + 1).unwrap_or(0)..].chars().count()
+    }
+
+    // starts a line, keeping one blank line if the source has some before that line
+    fn new_line(&mut self, line: usize, keep_blank: bool) {
+        if let Some(last) = self.last_line {
+            if keep_blank && (last + 1..line).any(|l| self.lines[l - 1].trim().is_empty()) {
+                self.out.push('\n')
+            }
+        }
+        if !self.out.is_empty() {
+            self.out.push('\n')
+        }
+        self.out.push_str(&INDENT.repeat(self.indent));
+        self.last_line = Some(line)
+    }
+
+    // comments on their own lines before a position
+    fn comments_before(&mut self, pos: usize, keep_blank: bool) {
+        while !self.comments.is_empty() && self.comments[0].pos < pos {
+            let comment = self.comments.remove(0);
+            self.new_line(comment.line, keep_blank);
+            self.push(&comment.text)
+        }
+    }
+
+    // comment at the end of a line, before the next statement or element
+    fn trailing_comment(&mut self, line: usize, next: usize) {
+        if !self.comments.is_empty() && self.comments[0].line == line && self.comments[0].pos < next {
+            let comment = self.comments.remove(0);
+            self.push(" ");
+            self.push(&comment.text)
+        }
+    }
+
+    fn statements(&mut self, pairs: Vec<Pair<Rule>>, end: usize) {
+        for (i, pair) in pairs.iter().enumerate() {
+            self.comments_before(pair.as_span().start(), true);
+            self.new_line(pair.line_col().0, true);
+            self.statement(pair.clone());
+            let line = pair.as_span().end_pos().line_col().0;
+            self.last_line = Some(line);
+            self.trailing_comment(line, pairs.get(i + 1).map(|p| p.as_span().start()).unwrap_or(end));
+        }
+        self.comments_before(end, true)
+    }
+
+    fn statement(&mut self, pair: Pair<Rule>) {
+        let rule = pair.as_rule();
+        if !matches!(rule, Rule::Declaration | Rule::Definition | Rule::Struct | Rule::Enum | Rule::Interface | Rule::Signature | Rule::Test | Rule::Assignment | Rule::IfElse | Rule::While) {
+            return self.expr(pair)
+        }
+        if rule == Rule::Enum {
+            return self.enumeration(pair)
+        }
+        let end = pair.as_span().end() - 1;
+        let mut inner = pair.into_inner();
+        match rule {
+            Rule::Declaration | Rule::Definition | Rule::Signature | Rule::Struct => {
+                let keyword = match rule {
+                    Rule::Declaration => inner.next().unwrap().as_str().to_owned(),
+                    Rule::Definition | Rule::Signature => "fun".to_owned(),
+                    _ => "struct".to_owned(),
+                };
+                let mut name = inner.next().unwrap();
+                if name.as_rule() == Rule::Receiver {
+                    self.push(&format!("{} {}.", keyword, type_expr(name.into_inner().next().unwrap())));
+                    name = inner.next().unwrap();
+                } else {
+                    self.push(&format!("{} ", keyword));
+                }
+                self.push(name.as_str());
+                for p in inner {
+                    match p.as_rule() {
+                        Rule::TypeParams => self.push(&format!("<{}>", p.into_inner().map(|s| s.as_str()).collect::<Vec<_>>().join(", "))),
+                        Rule::Parameters => self.push(&parameters(p)),
+                        Rule::RawType => self.push(&format!(": {}", type_expr(p))),
+                        Rule::Interfaces => self.push(&format!(" : {}", p.into_inner().map(|s| s.as_str()).collect::<Vec<_>>().join(", "))),
+                        Rule::Methods => {
+                            let end = p.as_span().end() - 1;
+                            self.push(" ");
+                            self.members(p.into_inner().collect(), end)
+                        }
+                        _ => { self.push(" = "); self.expr(p) }
+                    }
+                }
+            }
+            Rule::Interface => {
+                self.push(&format!("interface {} ", inner.next().unwrap().as_str()));
+                self.members(inner.collect(), end)
+            }
+            Rule::Test => {
+                self.push(&format!("test {} ", inner.next().unwrap().as_str()));
+                self.block(inner.next().unwrap())
+            }
+            Rule::Assignment => {
+                self.push(&format!("{} = ", inner.next().unwrap().as_str()));
+                self.expr(inner.next().unwrap())
+            }
+            _ => {
+                self.push(if_else!(rule == Rule::IfElse, "if (", "while ("));
+                self.expr(inner.next().unwrap());
+                self.push(") ");
+                self.expr(inner.next().unwrap());
+                if let Some(p) = inner.next() {
+                    self.push(" else ");
+                    self.expr(p)
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, pair: Pair<Rule>) {
+        match pair.as_rule() {
+            Rule::Expr => for p in pair.into_inner() {
+                match p.as_rule() {
+                    Rule::Dot => self.push("."),
+                    Rule::Expr => { self.push("("); self.expr(p); self.push(")") }
+                    Rule::Add | Rule::Sub | Rule::Mul | Rule::Div | Rule::Mod | Rule::Exp | Rule::Eq | Rule::Neq |
+                    Rule::Le | Rule::Lt | Rule::Ge | Rule::Gt | Rule::And | Rule::Or => self.push(&format!(" {} ", p.as_str())),
+                    _ => self.expr(p),
+                }
+            }
+            Rule::CallExpr => {
+                let mut inner = pair.into_inner();
+                self.push(inner.next().unwrap().as_str());
+                self.push("(");
+                for (i, p) in inner.enumerate() {
+                    self.push(if_else!(i == 0, "", ", "));
+                    self.expr(p)
+                }
+                self.push(")")
+            }
+            Rule::Block => self.block(pair),
+            Rule::Match => self.cases(pair),
+            Rule::Case => {
+                let mut inner = pair.into_inner();
+                self.push(&pattern(inner.next().unwrap()));
+                for p in inner {
+                    if p.as_rule() == Rule::Guard {
+                        self.push(" if ");
+                        self.expr(p.into_inner().next().unwrap())
+                    } else {
+                        self.push(" -> ");
+                        self.expr(p)
+                    }
+                }
+            }
+            Rule::Parameters => self.push(&parameters(pair)),
+            Rule::List | Rule::Map => self.collection(pair),
+            Rule::RawType => self.push(&format!(":{}", type_expr(pair))),
+            _ => self.push(pair.as_str()),
+        }
+    }
+
+    fn block(&mut self, pair: Pair<Rule>) {
+        let end = pair.as_span().end() - 1;
+        self.members(pair.into_inner().collect(), end)
+    }
+
+    // the statements of a block, the cases of a match or the methods of a structure, one per line between braces
+    fn members(&mut self, pairs: Vec<Pair<Rule>>, end: usize) {
+        self.push("{");
+        self.indent += 1;
+        self.last_line = None;
+        self.statements(pairs, end);
+        self.indent -= 1;
+        self.out.push('\n');
+        self.push(&format!("{}}}", INDENT.repeat(self.indent)))
+    }
+
+    // the cases of a match are indented like the statements of a block
+    fn cases(&mut self, pair: Pair<Rule>) {
+        let end = pair.as_span().end() - 1;
+        self.push(if_else!(pair.as_str().starts_with("when"), "when (", "match ("));
+        let mut inner = pair.into_inner();
+        self.expr(inner.next().unwrap());
+        self.push(") ");
+        self.members(inner.collect(), end)
+    }
+
+    // an enum on one line, or one variant per line when too long or commented
+    fn enumeration(&mut self, pair: Pair<Rule>) {
+        let span = pair.as_span();
+        let commented = self.comments.iter().any(|c| c.pos > span.start() && c.pos < span.end());
+        let mut inner = pair.into_inner();
+        self.push(&format!("enum {} {{", inner.next().unwrap().as_str()));
+        let variants: Vec<(Pair<Rule>, String)> = inner.map(|v| (v.clone(), variant(v))).collect();
+        let width: usize = variants.iter().map(|v| v.1.chars().count() + 2).sum();
+        let split = commented || self.column() + width + 2 > MAX_WIDTH;
+        self.indent += 1;
+        for (i, (pair, text)) in variants.iter().enumerate() {
+            if split {
+                self.comments_before(pair.as_span().start(), false);
+                self.new_line(pair.line_col().0, false);
+            }
+            self.push(&format!("{}{}{}", if_else!(split || i > 0, "", " "), text, if_else!(i + 1 < variants.len(), ",", "")));
+            if split {
+                let next = variants.get(i + 1).map(|v| v.0.as_span().start()).unwrap_or(span.end());
+                self.trailing_comment(pair.as_span().end_pos().line_col().0, next)
+            } else if i + 1 < variants.len() {
+                self.push(" ")
+            }
+        }
+        self.indent -= 1;
+        if split {
+            self.comments_before(span.end(), false);
+            self.out.push('\n');
+            self.push(&INDENT.repeat(self.indent))
+        } else {
+            self.push(" ")
+        }
+        self.push("}")
+    }
+
+    // a list or a map, split on several lines when too long or commented: one element per line,
+    // or as many as possible when the elements are not collections
+    fn collection(&mut self, pair: Pair<Rule>) {
+        let (open, close) = if_else!(pair.as_rule() == Rule::List, ("[", "]"), ("{", "}"));
+        let span = pair.as_span();
+        let commented = self.comments.iter().any(|c| c.pos > span.start() && c.pos < span.end());
+        let split = !self.flat && (commented || self.column() + self.measure(pair.clone()) > MAX_WIDTH);
+        let elements = self.elements(pair);
+        let fill = split && !commented && elements.iter().flat_map(|(k, v)| [Some(k), v.as_ref()]).flatten()
+            .all(|p| !p.clone().into_inner().any(|e| matches!(e.as_rule(), Rule::List | Rule::Map)));
+        self.push(open);
+        self.indent += 1;
+        for (i, (key, value)) in elements.iter().enumerate() {
+            let width = self.measure(key.clone()) + value.as_ref().map(|v| self.measure(v.clone()) + 2).unwrap_or(0);
+            if fill && i > 0 && self.column() + width + 2 <= MAX_WIDTH {
+                self.push(" ")
+            } else if split {
+                self.comments_before(key.as_span().start(), false);
+                self.new_line(key.line_col().0, false);
+            }
+            self.expr(key.clone());
+            if let Some(v) = value {
+                self.push(": ");
+                self.expr(v.clone())
+            }
+            if i + 1 < elements.len() {
+                self.push(if_else!(split, ",", ", "))
+            }
+            if split {
+                let next = elements.get(i + 1).map(|e| e.0.as_span().start()).unwrap_or(span.end());
+                self.trailing_comment(value.as_ref().unwrap_or(key).as_span().end_pos().line_col().0, next)
+            }
+        }
+        self.indent -= 1;
+        if split {
+            self.comments_before(span.end(), false);
+            self.out.push('\n');
+            self.push(&INDENT.repeat(self.indent))
+        }
+        self.push(close)
+    }
+
+    // elements of a list, or keys and values of a map
+    fn elements<'i>(&self, pair: Pair<'i, Rule>) -> Vec<(Pair<'i, Rule>, Option<Pair<'i, Rule>>)> {
+        let is_map = pair.as_rule() == Rule::Map;
+        let mut inner = pair.into_inner();
+        let mut elements = Vec::new();
+        while let Some(key) = inner.next() {
+            let value = if_else!(is_map, inner.next(), None);
+            elements.push((key, value))
+        }
+        elements
+    }
+
+    fn measure(&self, pair: Pair<Rule>) -> usize {
+        let mut formatter = Formatter::new(self.source, true);
+        formatter.expr(pair);
+        formatter.out.chars().count()
+    }
+}
+
+fn parameters(pair: Pair<Rule>) -> String {
+    let params: Vec<String> = pair.into_inner().map(|p| {
+        let mut inner = p.into_inner();
+        format!("{}: {}", inner.next().unwrap().as_str(), type_expr(inner.next().unwrap()))
+    }).collect();
+    format!("({})", params.join(", "))
+}
+
+// a pattern of a case, with a space after the commas
+fn pattern(pair: Pair<Rule>) -> String {
+    let (rule, str) = (pair.as_rule(), pair.as_str());
+    let inner: Vec<Pair<Rule>> = pair.into_inner().collect();
+    let patterns = |from: usize| inner[from..].iter().map(|p| pattern(p.clone())).collect::<Vec<_>>().join(", ");
+    match rule {
+        Rule::RangePattern => format!("{}..{}", inner[0].as_str(), inner[1].as_str()),
+        Rule::TypePattern => format!("is {}", type_expr(inner[1].clone())),
+        Rule::ListPattern => format!("[{}]", patterns(0)),
+        Rule::MapPattern => format!("{{{}}}", inner.chunks(2).map(|p| format!("{}: {}", p[0].as_str(), pattern(p[1].clone()))).collect::<Vec<_>>().join(", ")),
+        Rule::VariantPattern | Rule::NamePattern => {
+            let names = inner.iter().take_while(|p| p.as_rule() == Rule::Symbol).count();
+            let name = inner[..names].iter().map(|p| p.as_str()).collect::<Vec<_>>().join(".");
+            if_else!(rule == Rule::NamePattern, name, format!("{}({})", name, patterns(names)))
+        }
+        _ => str.to_owned(),
+    }
+}
+
+// a variant of an enum with its fields
+fn variant(pair: Pair<Rule>) -> String {
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str();
+    format!("{}{}", name, inner.next().map(parameters).unwrap_or_default())
+}
+
+// a type with a space after the commas and around the arrows
+fn type_expr(pair: Pair<Rule>) -> String {
+    let (rule, str) = (pair.as_rule(), pair.as_str());
+    let inner: Vec<String> = pair.into_inner().map(type_expr).collect();
+    match rule {
+        Rule::FunType => {
+            let (output, args) = inner.split_last().unwrap();
+            format!("({}) -> {}", args.join(", "), output)
+        }
+        Rule::TupleType => format!("({})", inner.join(", ")),
+        Rule::NamedType if inner.len() > 1 => format!("{}<{}>", inner[0], inner[1..].join(", ")),
+        Rule::RawType | Rule::TypeExpr | Rule::NamedType => inner.concat(),
+        _ => str.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!("val a: Int = 1 + 2 * (3 - 1)\n", format_source("val   a :Int=1+2*(3-1)").unwrap());
+        assert_eq!("fun inc(x: Int, y: Int): Int = {\n  val z = x.add(y)\n  z + 1\n}\n", format_source("fun inc(x:Int,y :Int):Int={ val z=x.add( y ); z+1 }").unwrap());
+        assert_eq!("if (a == 1) {\n  print(\"one\")\n} else 2\nwhile (a < 3) {\n  a = a + 1\n}\n", format_source("if (a==1) {print(\"one\")} else 2\nwhile(a<3){a=a+1}").unwrap());
+        assert_eq!("val m = {\"a\": [1, 2], \"b\": null}\n", format_source("val m = {\"a\":[1,2] ,\"b\": null}").unwrap());
+        assert_eq!("test \"inc\" {\n  assert(inc(1) == 2, \"inc\")\n}\n", format_source("test   \"inc\"{assert(inc(1)==2,\"inc\")}").unwrap());
+        assert_eq!("val m: Map<Str, List<(Int) -> Int?>> = x\n", format_source("val m:Map< Str,List<(Int)->Int?> > = x").unwrap());
+        assert_eq!("fun Pair<A, B>.first(): A = this.a\n", format_source("fun Pair<A,B> . first():A=this . a").unwrap());
+        assert_eq!("fun same<T>(x: T, y: T): T = y\n", format_source("fun same< T >(x:T,y:T):T=y").unwrap());
+        assert_eq!("enum Shape { Circle(r: Float), Rect(w: Float, h: Float) }\n", format_source("enum Shape{Circle(r:Float) ,Rect(w:Float,h:Float)}").unwrap());
+        assert_eq!("enum Color {\n  Red, # first\n  Green\n}\n", format_source("enum Color { Red, # first\n Green }").unwrap());
+        assert_eq!("val s = match (x) {\n  0 -> \"zero\"\n  1..9 if x != 5 -> \"small\" # not 5\n\n  [a, _] -> a\n  Shape.Circle(r, {\"a\": 1}) -> {\n    r\n  }\n  is List<Int> -> 1\n  else -> \"big\"\n}\n",
+                   format_source("val s = match(x){0->\"zero\";1 .. 9 if x!=5->\"small\" # not 5\n\n[a,_]->a\nShape . Circle(r,{\"a\":1})->{r}\nis List< Int > -> 1\nelse->\"big\"}").unwrap());
+        assert_eq!("interface Shape {\n  fun area(): Float\n  fun scale(k: Float): Shape\n}\nstruct Circle(r: Float) : Shape, Named {\n  fun area(): Float = 3.14 * this.r * this.r\n}\n",
+                   format_source("interface Shape{fun area():Float;fun scale(k:Float):Shape}\nstruct Circle(r:Float):Shape,Named{fun area():Float=3.14*this.r*this.r}").unwrap());
+        assert_eq!("", format_source("\n\n").unwrap());
+        assert_eq!(Err((1, 9)), format_source("val a = ").map_err(|e| (e.line, e.column)));
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "# header\n\n\nval a = 1 # one\n\n# block\nwhile (a < 2) { # loop\n  a = a + 1\n\n  # end\n}\nprint(\"#\") ; a #last\n";
+        assert_eq!("# header\n\nval a = 1 # one\n\n# block\nwhile (a < 2) {\n  # loop\n  a = a + 1\n\n  # end\n}\nprint(\"#\")\na #last\n", format_source(source).unwrap());
+    }
+
+    #[test]
+    fn test_long_collections() {
+        let names: Vec<String> = (1..=12).map(|i| format!("\"name{}\"", i)).collect();
+        assert_eq!("val names = [\n  \"name1\", \"name2\", \"name3\", \"name4\", \"name5\", \"name6\", \"name7\", \"name8\", \"name9\", \"name10\",\n  \"name11\", \"name12\"\n]\n",
+                   format_source(&format!("val names = [{}]", names.join(","))).unwrap());
+        assert_eq!("val m = [\n  [\n    \"name1\", \"name2\", \"name3\", \"name4\", \"name5\", \"name6\", \"name7\", \"name8\", \"name9\", \"name10\",\n    \"name11\", \"name12\"\n  ],\n  [1]\n]\n",
+                   format_source(&format!("val m = [[{}], [1]]", names.join(","))).unwrap());
+        assert_eq!("val l = [\n  1,\n  2, # two\n  3\n]\n", format_source("val l = [1,\n 2, # two\n 3]").unwrap());
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut files = Vec::new();
+        find_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("samples"), ".gro", &mut files);
+        assert!(!files.is_empty());
+        for file in files {
+            let source = fs::read_to_string(&file).unwrap();
+            let formatted = format_source(&source).unwrap_or_else(|e| panic!("{}: {:?}", file.display(), e));
+            assert_eq!(formatted, format_source(&formatted).unwrap(), "{}", file.display());
+            assert_eq!(source, formatted, "{} is not formatted", file.display());
+        }
+    }
+}
@@ -1,25 +1,62 @@
-use std::fmt::Debug;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
 use std::io;
+use std::ptr;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
 
 use crate::exception::Exception;
+use crate::files;
+use crate::json;
+use crate::patterns;
 use crate::expr::Expr;
-use crate::expr::Expr::{Bool, Float, Fun, Int, Nil, Symbol};
+use crate::expr::Expr::{Bool, Failure, Float, Fun, Int, Nil, Symbol};
 use crate::if_else;
-use crate::scope::Scope;
+use crate::interrupt;
+use crate::scope::{Env, Scope};
 use crate::types::Type;
+use crate::utils::{language, Resources};
 
-use self::Function::{BuiltIn, Defined, Stateful, Stateless};
+use self::Function::{BuiltIn, Defined, Native, Stateful, Stateless};
 
 macro_rules! def {
     ($scope:expr, $name:expr, $sign:expr, $lambda:expr) => {  $scope.add_fun(Fun($name.to_owned(), Type::from_str($sign).unwrap(), $lambda)) };
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// native Rust closure registered by an embedding application
+#[derive(Clone)]
+pub struct NativeFn(pub Rc<NativeLambda>);
+
+pub type NativeLambda = dyn Fn(&[Expr]) -> Result<Expr, Exception>;
+
+impl Debug for NativeFn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { write!(f, "NativeFn") }
+}
+
+#[derive(Debug, Clone)]
 pub enum Function {
     Stateless(fn(&Vec<Expr>) -> Result<Expr, Exception>),
     Stateful(fn(&Vec<Expr>, &Scope) -> Result<Expr, Exception>),
     BuiltIn(fn(&Vec<Expr>, &mut Scope) -> Result<Expr, Exception>),
-    Defined(Vec<String>, Box<Expr>),
+    // the parameters, the body and the local frames enclosing the definition, none in the global scope
+    Defined(Vec<String>, Rc<Expr>, Option<Env>),
+    Native(NativeFn),
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Stateless(f), Stateless(g)) => ptr::fn_addr_eq(*f, *g),
+            (Stateful(f), Stateful(g)) => ptr::fn_addr_eq(*f, *g),
+            (BuiltIn(f), BuiltIn(g)) => ptr::fn_addr_eq(*f, *g),
+            (Defined(p, b, e), Defined(q, c, f)) => p == q && b == c && e == f,
+            (Native(f), Native(g)) => Rc::ptr_eq(&f.0, &g.0),
+            _ => false,
+        }
+    }
 }
 
 impl Function {
@@ -27,14 +64,17 @@ impl Function {
         match self {
             Stateless(f) => f(vec),
             Stateful(f) => f(vec, scope),
-            Defined(params, body) => apply_defined(scope, body, params, vec),
+            Defined(params, body, closure) => apply_defined(scope, body, params, closure.as_ref(), vec),
+            Native(f) => (f.0)(vec),
             _ => panic!("Cannot apply a Mutating function"),
         }
     }
 }
 
-fn apply_defined(scope: &Scope, body: &Box<Expr>, params: &Vec<String>, vec: &Vec<Expr>) -> Result<Expr, Exception> {
-    let mut local = scope.child();
+// the body is evaluated where the function is defined, not where it is called
+fn apply_defined(scope: &Scope, body: &Expr, params: &[String], closure: Option<&Env>, vec: &[Expr]) -> Result<Expr, Exception> {
+    interrupt::check()?;
+    let mut local = scope.call(closure);
     local.add_args(params, vec);
     body.eval_mutable(&mut local)
 }
@@ -43,6 +83,15 @@ pub fn add_functions(sc: &mut Scope) {
     // Any functions
     def!(sc, "to_str", "(Any)->Str", Stateless(|vec| Ok(Expr::Str(vec[0].print()))));
 
+    // Conversion functions, the parsing of a string fails when it is not a valid value
+    def!(sc, "Any.toStr", "(Any)->Str", Stateless(|vec| Ok(Expr::Str(match &vec[0] { Expr::Str(s) => s.clone(), other => other.print() }))));
+    def!(sc, "Str.toInt", "(Str)->Int!", Stateless(|vec| Ok(parse_value(vec[0].to_str()?, "Int", |s| s.parse().ok().map(Int)))));
+    def!(sc, "Str.toFloat", "(Str)->Float!", Stateless(|vec| Ok(parse_value(vec[0].to_str()?, "Float", |s| s.parse().ok().filter(|f: &f64| f.is_finite()).map(Float)))));
+    def!(sc, "Str.toBool", "(Str)->Bool!", Stateless(|vec| Ok(parse_value(vec[0].to_str()?, "Bool", |s| s.parse().ok().map(Bool)))));
+    def!(sc, "Int.toFloat", "(Int)->Float", Stateless(|vec| Ok(Float(i64::try_from(vec[0].clone())? as f64))));
+    def!(sc, "Float.toInt", "(Float)->Int", Stateless(|vec| to_int(f64::try_from(vec[0].clone())?, "trunc")));
+    def!(sc, "Float.toInt", "(Float,Str)->Int", Stateless(|vec| to_int(f64::try_from(vec[0].clone())?, vec[1].to_str()?)));
+
     // Number functions
     let sign = "(Number,Number)->Number";
     def!(sc, "Number.add", sign, Stateless(|vec| NumberFun::Add.eval(&vec[0], &vec[1])));
@@ -51,77 +100,314 @@ pub fn add_functions(sc: &mut Scope) {
     def!(sc, "Number.div", sign, Stateless(|vec| NumberFun::Div.eval(&vec[0], &vec[1])));
     def!(sc, "Number.mod", sign, Stateless(|vec| NumberFun::Mod.eval(&vec[0], &vec[1])));
     let sign = "(Number,Number)->Bool";
-    def!(sc, "Number.eq", sign, Stateless(|vec| NumberFun::Eq.eval(&vec[0], &vec[1])));
-    def!(sc, "Number.neq", sign, Stateless(|vec| NumberFun::Neq.eval(&vec[0], &vec[1])));
     def!(sc, "Number.ge", sign, Stateless(|vec| NumberFun::Ge.eval(&vec[0], &vec[1])));
     def!(sc, "Number.gt", sign, Stateless(|vec| NumberFun::Gt.eval(&vec[0], &vec[1])));
     def!(sc, "Number.lt", sign, Stateless(|vec| NumberFun::Lt.eval(&vec[0], &vec[1])));
     def!(sc, "Number.le", sign, Stateless(|vec| NumberFun::Le.eval(&vec[0], &vec[1])));
 
-    // Boolean Functions
-    let sign = "(Bool,Bool)->Bool";
-    def!(sc, "Bool.and", sign, Stateless(|vec| Ok(Bool(vec[0].to_bool()? && vec[1].to_bool()?))));
-    def!(sc, "Bool.or", sign, Stateless(|vec| Ok(Bool(vec[0].to_bool()? || vec[1].to_bool()?))));
-
+    // Comparisons of any values, the ordering fails for the values that are not comparable
+    let sign = "(Any,Any)->Bool";
+    def!(sc, "Any.eq", sign, Stateless(|vec| Ok(Bool(equals(&vec[0], &vec[1])))));
+    def!(sc, "Any.neq", sign, Stateless(|vec| Ok(Bool(!equals(&vec[0], &vec[1])))));
+    def!(sc, "Any.ge", sign, Stateful(|vec, scope| Ok(Bool(compare(&vec[0], &vec[1], scope)?.is_ge()))));
+    def!(sc, "Any.gt", sign, Stateful(|vec, scope| Ok(Bool(compare(&vec[0], &vec[1], scope)?.is_gt()))));
+    def!(sc, "Any.lt", sign, Stateful(|vec, scope| Ok(Bool(compare(&vec[0], &vec[1], scope)?.is_lt()))));
+    def!(sc, "Any.le", sign, Stateful(|vec, scope| Ok(Bool(compare(&vec[0], &vec[1], scope)?.is_le()))));
 
     // String functions
     def!(sc, "Str.read", "(Str)->Expr", Stateful(|vec, scope| Ok(scope.read(vec[0].to_str()?))));
     def!(sc, "Str.trim", "(Str)->Str", Stateless(|vec| Ok(Expr::Str(vec[0].to_str()?.trim().to_owned()))));
 
+    // List functions
+    let generic = |sign: &str| Type::from_str(sign).unwrap().with_params(&["T".to_owned()]);
+    sc.add_fun(Fun("List.sorted".to_owned(), generic("(List<T>)->List<T>"), Stateful(|vec, scope| sorted(&vec[0], scope))));
+    sc.add_fun(Fun("List.min".to_owned(), generic("(List<T>)->T?"), Stateful(|vec, scope| extremum(&vec[0], Ordering::Less, scope))));
+    sc.add_fun(Fun("List.max".to_owned(), generic("(List<T>)->T?"), Stateful(|vec, scope| extremum(&vec[0], Ordering::Greater, scope))));
+
+    // Map functions
+    def!(sc, "Map.get", "(Map<Any,Any>,Any)->Any?", Stateless(|vec| match &vec[0] {
+        Expr::Map(_, pairs) => Ok(pairs.iter().find(|p| equals(&p.0, &vec[1])).map(|p| p.1.clone()).unwrap_or(Nil)),
+        other => Err(Exception::NotA("Map".to_owned(), other.print())),
+    }));
+
     // IO functions
     def!(sc, "readLine", "()->Any", Stateless(|_| read_line()));
-    def!(sc, "print", "(Macro)->Any", Stateless(|vec,| print(vec)));
+    def!(sc, "print", "(Macro)->Any", Stateless(print));
     def!(sc, "eval", "(Any)->Any", Stateful(|vec, scope| vec[0].eval(scope)));
 
+    // File functions
+    def!(sc, "readFile", "(Str)->Str!", Stateful(|vec, scope| files::read_file(vec[0].to_str()?, scope)));
+    def!(sc, "readLines", "(Str)->List<Str>!", Stateful(|vec, scope| files::read_lines(vec[0].to_str()?, scope)));
+    def!(sc, "writeFile", "(Str,Str)->Int!", Stateful(|vec, scope| files::write_file(vec[0].to_str()?, vec[1].to_str()?, scope)));
+    def!(sc, "appendFile", "(Str,Str)->Int!", Stateful(|vec, scope| files::append_file(vec[0].to_str()?, vec[1].to_str()?, scope)));
+    def!(sc, "fileExists", "(Str)->Bool!", Stateful(|vec, scope| files::file_exists(vec[0].to_str()?, scope)));
+    def!(sc, "listDir", "(Str)->List<Str>!", Stateful(|vec, scope| files::list_dir(vec[0].to_str()?, scope)));
+    def!(sc, "deleteFile", "(Str)->Bool!", Stateful(|vec, scope| files::delete_file(vec[0].to_str()?, scope)));
+
+    // JSON functions
+    def!(sc, "Str.parseJson", "(Str)->Any!", Stateless(|vec| Ok(json::parse_json(vec[0].to_str()?))));
+    def!(sc, "toJson", "(Any,Bool)->Str", Stateless(|vec| Ok(Expr::Str(json::to_json(&vec[0], vec[1].to_bool()?)?))));
+
+    // Try functions
+    def!(sc, "Any.isFailure", "(Any)->Bool", Stateless(|vec| Ok(Bool(vec[0].is_failure()))));
+    def!(sc, "Any.isSuccess", "(Any)->Bool", Stateless(|vec| Ok(Bool(!vec[0].is_failure()))));
+    def!(sc, "Any.getOrElse", "(Any,Any)->Any", Stateless(|vec| Ok(if_else!(vec[0].is_failure(), vec[1].clone(), vec[0].clone()))));
+
+
+    // Test functions
+    def!(sc, "assert", "(Bool,Str)->Any", Stateless(|vec| if_else!(vec[0].to_bool()?, Ok(Nil), Err(Exception::AssertionFailed(vec[1].to_str()?.to_owned())))));
+    def!(sc, "assertEquals", "(Any,Any)->Any", Stateless(|vec| assert_equals(&vec[0], &vec[1])));
+
+    // Documentation
+    def!(sc, "help", "Macro", BuiltIn(|vec, scope| help(vec.first().map(|e| e.to_symbol().or(e.to_str())).unwrap_or(Ok("help"))?, scope)));
 
     // macros
-    def!(sc, "const", "Macro", BuiltIn(|vec, scope| def_variable(vec[0].to_symbol()?, vec[2].eval(scope)?.expect(vec[1].to_type()?)?, scope, None)));
-    def!(sc, "var", "Macro", BuiltIn(|vec, scope| def_variable(vec[0].to_symbol()?, vec[2].eval(scope)?.expect(vec[1].to_type()?)?, scope, Some(true))));
-    def!(sc, "val", "Macro", BuiltIn(|vec, scope| def_variable(vec[0].to_symbol()?, vec[2].eval(scope)?.expect(vec[1].to_type()?)?, scope, Some(false))));
-    def!(sc, "fun", "Macro", BuiltIn(|vec, scope| def_function(vec[0].to_symbol()?, vec[1].to_params()?, vec[2].to_type()?, &vec[3], scope)));
-    def!(sc, "struct", "Macro", BuiltIn(|vec, scope| def_struct(vec[0].to_symbol()?, vec[1].to_params()?, scope)));
+    def!(sc, "const", "Macro", BuiltIn(|vec, scope| def_variable(vec[0].to_symbol()?, vec[1].to_type()?, vec[2].eval(scope)?, scope, None)));
+    def!(sc, "var", "Macro", BuiltIn(|vec, scope| def_variable(vec[0].to_symbol()?, vec[1].to_type()?, vec[2].eval(scope)?, scope, Some(true))));
+    def!(sc, "val", "Macro", BuiltIn(|vec, scope| def_variable(vec[0].to_symbol()?, vec[1].to_type()?, vec[2].eval(scope)?, scope, Some(false))));
+    def!(sc, "fun", "Macro", BuiltIn(|vec, scope| def_function(vec[0].to_symbol()?, vec[1].to_params()?, vec[2].to_type()?, &vec[3], vec.get(4), scope)));
+    def!(sc, "struct", "Macro", BuiltIn(|vec, scope| def_struct(vec[0].to_symbol()?, vec[1].to_params()?, vec[2].to_type()?, &vec[3..], scope)));
+    def!(sc, "interface", "Macro", BuiltIn(|vec, scope| def_interface(vec[0].to_symbol()?, &vec[1..], scope)));
+    def!(sc, "enum", "Macro", BuiltIn(|vec, scope| def_enum(vec[0].to_symbol()?, &vec[1..], scope)));
+    def!(sc, "match", "Macro", BuiltIn(|vec, scope| patterns::run_match(vec, scope)));
     def!(sc, "assign", "Macro", BuiltIn(|vec, scope| assign(vec[0].to_symbol()?, vec[1].eval_mutable(scope)?, scope)));
     def!(sc, "while", "Macro", BuiltIn(|vec, scope| run_while(&vec[0], vec, scope)));
+    def!(sc, "test", "Macro", BuiltIn(|vec, scope| vec[1].eval_mutable(scope)));
+    def!(sc, "and", "Macro", BuiltIn(|vec, scope| short_circuit("and", vec, scope)));
+    def!(sc, "or", "Macro", BuiltIn(|vec, scope| short_circuit("or", vec, scope)));
     def!(sc, "if", "Macro", BuiltIn(|vec, scope| if_else!(vec[0].eval_mutable(scope)?.to_bool()?, vec[1].eval_mutable(scope),vec[2].eval_mutable(scope))));
 
 }
 
 
-fn def_variable(name: &str, value: Expr, scope: &mut Scope, is_mutable: Option<bool>) -> Result<Expr, Exception> {
-    if scope.is_defined(&name, is_mutable.is_none()) {
+fn def_variable(name: &str, declared: &Type, value: Expr, scope: &mut Scope, is_mutable: Option<bool>) -> Result<Expr, Exception> {
+    if scope.is_defined(name, is_mutable.is_none()) {
         Err(Exception::AlreadyDefined(name.to_owned()))
     } else {
-        scope.set(name, value, is_mutable);
+        scope.set(name, value.expect(declared, scope)?, is_mutable);
+        if declared.is_defined() {
+            scope.set_type(name, declared)
+        }
         Ok(Symbol(name.to_owned()))
     }
 }
 
-fn def_function(name: &str, params: &Vec<(String, Type)>, output: &Type, expr: &Expr, scope: &mut Scope) -> Result<Expr, Exception> {
-    if scope.is_defined(&name, name.contains(".")) {
+fn def_function(name: &str, params: &[(String, Type)], output: &Type, expr: &Expr, doc: Option<&Expr>, scope: &mut Scope) -> Result<Expr, Exception> {
+    let types = Type::Fun(params.iter().map(|p| p.1.clone()).collect(), Box::new(output.clone()));
+    // the methods are declared in the global scope, where they are looked up, and close over the local frames
+    let (mut scope, closure) = if_else!(name.contains("."), (scope.global(), scope.closure()), (scope.clone(), None));
+    if scope.is_defined_fun(name, &types, false) {
         Err(Exception::AlreadyDefined(name.to_owned()))
     } else {
-        let types = Type::Fun(params.iter().map(|p| p.1.clone()).collect(), Box::new(output.clone()));
-        scope.add_fun(Fun(name.to_owned(), types, Defined(params.iter().map(|p| p.0.clone()).collect(), Box::new(expr.as_block()))));
+        let key = scope.add_fun(Fun(name.to_owned(), types, Defined(params.iter().map(|p| p.0.clone()).collect(), Rc::new(expr.as_block()), closure)));
+        if let Some(doc) = doc {
+            scope.set_doc(&key, doc.to_str()?)
+        }
         Ok(Symbol(name.to_owned()))
     }
 }
 
-fn def_struct(name: &str, params: &Vec<(String, Type)>, scope: &mut Scope) -> Result<Expr, Exception> {
-    if scope.is_defined(&name, true) {
-        Err(Exception::AlreadyDefined(name.to_owned()))
-    } else {
-        scope.set(name, Expr::Struct(name.to_owned(), params.clone()), None);
-        Ok(Symbol(name.to_owned()))
+// the fields are followed by the interfaces of the structure, the methods of its body and its doc comment
+fn def_struct(name: &str, params: &[(String, Type)], struct_type: &Type, members: &[Expr], scope: &mut Scope) -> Result<Expr, Exception> {
+    if scope.is_defined(name, true) {
+        return Err(Exception::AlreadyDefined(name.to_owned()))
     }
+    let interfaces: Vec<String> = members.iter().filter_map(|m| m.to_type().ok()).map(|t| t.print()).collect();
+    let methods: Vec<&Expr> = members.iter().filter(|m| matches!(m, Expr::Call(..))).collect();
+    check_interfaces(struct_type, &interfaces, &methods, scope)?;
+    if let Some(Expr::Str(doc)) = members.last() {
+        scope.set_doc(name, doc)
+    }
+    let type_params = match struct_type {
+        Type::Struct(_, args) => args.clone(),
+        _ => vec!(),
+    };
+    scope.set(name, Expr::Struct(name.to_owned(), type_params, params.to_vec(), interfaces), None);
+    for method in methods {
+        method.eval_mutable(scope)?;
+    }
+    Ok(Symbol(name.to_owned()))
+}
+
+// each method of the interfaces must be defined in the body of the structure or as a method of its type,
+// with the same arguments and a subtype of the output, which can be the structure where the interface is expected
+fn check_interfaces(struct_type: &Type, interfaces: &[String], methods: &[&Expr], scope: &Scope) -> Result<(), Exception> {
+    let signature = |method: &Expr| match method {
+        Expr::Call(_, args) => Some((args[0].to_symbol().ok()?.to_owned(), Type::Fun(args[1].to_params().ok()?.iter().map(|p| p.1.clone()).collect(), Box::new(args[2].to_type().ok()?.clone())))),
+        _ => None,
+    };
+    let defined: Vec<(String, Type)> = methods.iter().filter_map(|m| signature(m)).collect();
+    for interface in interfaces {
+        let Some(Expr::Interface(_, required)) = scope.find(interface) else {
+            return Err(Exception::NotA("Interface".to_owned(), interface.to_owned()))
+        };
+        for (method, types) in &required {
+            let name = struct_type.method_name(method);
+            let signatures: Vec<Type> = defined.iter().filter(|d| d.0 == name).map(|d| d.1.clone())
+                .chain(scope.find_overloads(&name).iter().map(|f| f.1.value_type())).collect();
+            let implemented = signatures.iter().any(|t| match (t, types) {
+                (Type::Fun(inputs, output), Type::Fun(args, expected)) => inputs[1..] == args[..] && (output.is_subtype_of(expected, scope)
+                    || **output == *struct_type && matches!(&**expected, Type::Struct(i, _) if interfaces.contains(i))),
+                _ => false,
+            });
+            if !implemented {
+                return Err(Exception::NotImplemented(struct_type.print(), format!("{}.{}", interface, method)))
+            }
+        }
+    }
+    Ok(())
 }
 
+// the methods of an interface are declared with the interface as receiver, they are dispatched to the method
+// of the structure
+fn def_interface(name: &str, args: &[Expr], scope: &mut Scope) -> Result<Expr, Exception> {
+    if scope.is_defined(name, true) {
+        return Err(Exception::AlreadyDefined(name.to_owned()))
+    }
+    let (doc, signatures) = match args.split_last() {
+        Some((Expr::Str(doc), signatures)) => (Some(doc), signatures),
+        _ => (None, args),
+    };
+    let methods = signatures.iter().map(|s| match s {
+        Expr::Call(method, args) => Ok((method.to_owned(), Type::Fun(args[0].to_params()?.iter().map(|p| p.1.clone()).collect(), Box::new(args[1].to_type()?.clone())))),
+        _ => Err(Exception::NotA("Signature".to_owned(), s.print())),
+    }).collect::<Result<Vec<_>, _>>()?;
+    if let Some(doc) = doc {
+        scope.set_doc(name, doc)
+    }
+    let interface = Type::Struct(name.to_owned(), vec!());
+    for (method, types) in &methods {
+        let Type::Fun(args, output) = types else { continue };
+        let method = interface.method_name(method);
+        let missing = method.clone();
+        let lambda = move |vec: &[Expr]| Err(Exception::NotImplemented(vec[0].get_type().print(), missing.clone()));
+        scope.add_fun(Fun(method, Type::Fun(std::iter::once(interface.clone()).chain(args.iter().cloned()).collect(), output.clone()), Native(NativeFn(Rc::new(lambda)))));
+    }
+    scope.set(name, Expr::Interface(name.to_owned(), methods), None);
+    Ok(Symbol(name.to_owned()))
+}
 
-fn assign(name: &str, value: Expr, scope: &mut Scope) -> Result<Expr, Exception> {
-    match scope.is_mutable(&name) {
-        None  => Err(Exception::NotDefined(name.to_owned())),
+// a variant without fields is a constant, the name of a variant with fields is its constructor. A variant declared
+// by several enums is qualified by its enum like 'Light.Red'. The variants are equal when they have the same fields,
+// and ordered like in their declaration
+fn def_enum(name: &str, args: &[Expr], scope: &mut Scope) -> Result<Expr, Exception> {
+    let (doc, variants) = match args.split_last() {
+        Some((Expr::Str(doc), variants)) => (Some(doc), variants),
+        _ => (None, args),
+    };
+    let variants = variants.iter().map(|v| match v {
+        Expr::Call(variant, fields) => Ok((variant.to_owned(), fields.first().map(|f| f.to_params().cloned()).transpose()?.unwrap_or_default())),
+        _ => Err(Exception::NotA("Variant".to_owned(), v.print())),
+    }).collect::<Result<Vec<_>, _>>()?;
+    if scope.is_defined(name, true) {
+        return Err(Exception::AlreadyDefined(name.to_owned()))
+    }
+    if let Some(doc) = doc {
+        scope.set_doc(name, doc)
+    }
+    scope.set(name, Expr::Enum(name.to_owned(), variants), None);
+    Ok(Symbol(name.to_owned()))
+}
+
+// the spaces around the string are ignored, so that the result of readLine can be parsed
+fn parse_value(str: &str, target: &str, parse: fn(&str) -> Option<Expr>) -> Expr {
+    parse(str.trim()).unwrap_or_else(|| Failure(Exception::CannotConvert(str.to_owned(), target.to_owned())))
+}
+
+// the rounding is explicit: toward zero (trunc), down (floor), up (ceil) or to the nearest integer (round)
+fn to_int(x: f64, mode: &str) -> Result<Expr, Exception> {
+    let rounded = match mode {
+        "trunc" => x.trunc(),
+        "floor" => x.floor(),
+        "ceil" => x.ceil(),
+        "round" => x.round(),
+        _ => return Err(Exception::UnknownRoundingMode(mode.to_owned())),
+    };
+    if_else!(rounded.is_finite() && rounded >= i64::MIN as f64 && rounded < i64::MAX as f64,
+        Ok(Int(rounded as i64)), Err(Exception::CannotConvert(x.to_string(), "Int".to_owned())))
+}
+
+// structural equality: the numbers are compared by value, the collections, structures and variants by their elements
+pub fn equals(x: &Expr, y: &Expr) -> bool {
+    let all_equal = |a: &[(String, Expr)], b: &[(String, Expr)]| a.len() == b.len() && a.iter().zip(b).all(|(u, v)| u.0 == v.0 && equals(&u.1, &v.1));
+    match (x, y) {
+        (Int(a), Int(b)) => a == b,
+        (Int(_) | Float(_), Int(_) | Float(_)) => f64::try_from(x.clone()) == f64::try_from(y.clone()),
+        (Expr::List(_, a), Expr::List(_, b)) => a.len() == b.len() && a.iter().zip(b).all(|(u, v)| equals(u, v)),
+        (Expr::Map(_, a), Expr::Map(_, b)) => a.len() == b.len() && a.iter().all(|(k, v)| b.iter().any(|(l, w)| equals(k, l) && equals(v, w))),
+        (Expr::Instance(Type::Struct(n, _), a), Expr::Instance(Type::Struct(m, _), b)) => n == m && all_equal(a, b),
+        (Expr::Variant(t, n, a), Expr::Variant(u, m, b)) => t == u && n == m && all_equal(a, b),
+        _ => x == y,
+    }
+}
+
+// the ordering of numbers, strings (lexicographic), booleans (false before true), variants (in the order of their
+// declaration) and lists (element by element, a list before the longer ones it starts)
+pub fn compare(x: &Expr, y: &Expr, scope: &Scope) -> Result<Ordering, Exception> {
+    match (x, y) {
+        (Int(a), Int(b)) => Ok(a.cmp(b)),
+        (Int(_) | Float(_), Int(_) | Float(_)) => Ok(f64::try_from(x.clone())?.partial_cmp(&f64::try_from(y.clone())?).unwrap_or(Ordering::Equal)),
+        (Expr::Str(a), Expr::Str(b)) => Ok(a.cmp(b)),
+        (Bool(a), Bool(b)) => Ok(a.cmp(b)),
+        (Expr::Variant(t, ..), Expr::Variant(u, ..)) if t == u => {
+            let position = |value: &Expr| match (value, scope.find(&t.print())) {
+                (Expr::Variant(_, name, _), Some(Expr::Enum(_, variants))) => variants.iter().position(|v| v.0 == *name),
+                _ => None,
+            }.ok_or_else(|| Exception::NotA("Variant".to_owned(), value.print()));
+            Ok(position(x)?.cmp(&position(y)?))
+        }
+        (Expr::List(_, a), Expr::List(_, b)) => {
+            for (u, v) in a.iter().zip(b) {
+                let ordering = compare(u, v, scope)?;
+                if ordering.is_ne() {
+                    return Ok(ordering)
+                }
+            }
+            Ok(a.len().cmp(&b.len()))
+        }
+        _ => Err(Exception::CannotCompare(x.value_type().print(), y.value_type().print())),
+    }
+}
+
+fn sorted(list: &Expr, scope: &Scope) -> Result<Expr, Exception> {
+    let Expr::List(t, values) = list else { return Err(Exception::NotA("List".to_owned(), list.print())) };
+    Ok(Expr::List(t.clone(), merge_sort(values.clone(), scope)?))
+}
+
+// a stable sort stopping at the first elements which cannot be compared
+fn merge_sort(mut values: Vec<Expr>, scope: &Scope) -> Result<Vec<Expr>, Exception> {
+    if values.len() <= 1 {
+        return Ok(values)
+    }
+    let right = merge_sort(values.split_off(values.len() / 2), scope)?;
+    let left = merge_sort(values, scope)?;
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        merged.extend(if_else!(compare(b, a, scope)?.is_lt(), right.next(), left.next()));
+    }
+    merged.extend(left.chain(right));
+    Ok(merged)
+}
+
+// the lowest or the highest element of a list, nil when it is empty
+fn extremum(list: &Expr, wanted: Ordering, scope: &Scope) -> Result<Expr, Exception> {
+    let Expr::List(_, values) = list else { return Err(Exception::NotA("List".to_owned(), list.print())) };
+    values.iter().try_fold(Nil, |best, value| Ok(match best {
+        Nil => value.clone(),
+        best => if_else!(compare(value, &best, scope)? == wanted, value.clone(), best),
+    }))
+}
+
+// the variable is changed in the scope declaring it, which can enclose the current one
+fn assign(name: &str, value: Expr, scope: &Scope) -> Result<Expr, Exception> {
+    let Some(mut scope) = scope.declaring(name) else {
+        return Err(Exception::NotDefined(name.to_owned()))
+    };
+    match scope.is_mutable(name) {
         Some(false) => Err(Exception::NotMutable(name.to_owned())),
-        _ if scope.get_type(name) != value.get_type() => Err(Exception::UnexpectedType(value.get_type().to_string())),
         _ => {
+            let value = value.expect(&scope.get_type(name), &scope)?;
             scope.set(name, value.clone(), None);
             Ok(value)
         }
@@ -135,8 +421,6 @@ pub enum NumberFun {
     Add,
     Sub,
     Mod,
-    Eq,
-    Neq,
     Gt,
     Ge,
     Lt,
@@ -159,8 +443,6 @@ impl NumberFun {
             NumberFun::Mul => Ok(Int(a * b)),
             NumberFun::Mod => if_else!(b != 0, Ok(Int(a % b)), Err(Exception::DivisionByZero)),
             NumberFun::Div => if_else!(b != 0, Ok(Int(a / b)), Err(Exception::DivisionByZero)),
-            NumberFun::Eq => Ok(Bool(a == b)),
-            NumberFun::Neq => Ok(Bool(a != b)),
             NumberFun::Gt => Ok(Bool(a > b)),
             NumberFun::Ge => Ok(Bool(a >= b)),
             NumberFun::Lt => Ok(Bool(a < b)),
@@ -174,8 +456,6 @@ impl NumberFun {
             NumberFun::Mul => Ok(Float(a * b)),
             NumberFun::Mod => if_else!(b != 0.0, Ok(Float(a % b)), Err(Exception::DivisionByZero)),
             NumberFun::Div => if_else!(b != 0.0, Ok(Float(a / b)), Err(Exception::DivisionByZero)),
-            NumberFun::Eq => Ok(Bool(a == b)),
-            NumberFun::Neq => Ok(Bool(a != b)),
             NumberFun::Gt => Ok(Bool(a > b)),
             NumberFun::Ge => Ok(Bool(a >= b)),
             NumberFun::Lt => Ok(Bool(a < b)),
@@ -190,10 +470,81 @@ fn print(vec: &Vec<Expr>) -> Result<Expr, Exception> {
     Ok(Nil)
 }
 
+lazy_static! {
+    // the resources in the language of the system, loaded at the first call to help
+    static ref RESOURCES: Resources = Resources::init(&language());
+}
+
+// the documentation is returned, the REPL displays it
+fn help(name: &str, scope: &Scope) -> Result<Expr, Exception> {
+    Ok(Expr::Str(describe(name, scope, &RESOURCES)?))
+}
+
+/// Signatures and descriptions of the functions, methods, structure or enum with that name.
+/// The description is the doc comment of a declaration, or the localized one of a builtin.
+pub fn describe(name: &str, scope: &Scope, resources: &Resources) -> Result<String, Exception> {
+    let suffix = format!(".{}", name);
+    let mut docs = Vec::new();
+    match scope.find(name) {
+        Some(value @ (Expr::Struct(..) | Expr::Enum(..) | Expr::Interface(..))) => docs.push((type_declaration(&value), scope.find_doc(name))),
+        Some(Expr::Variant(t, _, _)) => docs.push((type_declaration(&scope.find(&t.print()).unwrap_or(Nil)), scope.find_doc(&t.print()))),
+        _ => {}
+    }
+    for fun in scope.global().find_funs("").iter().filter(|n| *n == name || n.ends_with(&suffix)) {
+        for (key, value) in scope.find_overloads(fun) {
+            if let Fun(_, types, _) = value {
+                let signature = if_else!(types == Type::Macro, fun.to_owned(), format!("{}: {}", fun, types.print()));
+                docs.push((signature, scope.find_doc(&key).or(resources.get_doc(fun).cloned())));
+            }
+        }
+    }
+    if docs.is_empty() {
+        return Err(Exception::UndefinedSymbol(name.to_owned()))
+    }
+    Ok(docs.iter().map(|(signature, doc)| match doc {
+        Some(doc) => format!("{}\n{}", signature, doc.lines().map(|l| format!("  {}", l)).collect::<Vec<_>>().join("\n")),
+        None => signature.to_owned(),
+    }).collect::<Vec<_>>().join("\n"))
+}
+
+// a structure, an enum or an interface printed like its declaration
+pub fn type_declaration(value: &Expr) -> String {
+    let fields = |fields: &[(String, Type)]| fields.iter().map(|f| format!("{}: {}", f.0, f.1.print())).collect::<Vec<_>>().join(", ");
+    match value {
+        Expr::Struct(name, params, fields_types, interfaces) => {
+            let params = if_else!(params.is_empty(), String::new(), format!("<{}>", params.iter().map(|t| t.print()).collect::<Vec<_>>().join(", ")));
+            let interfaces = if_else!(interfaces.is_empty(), String::new(), format!(" : {}", interfaces.join(", ")));
+            format!("struct {}{}({}){}", name, params, fields(fields_types), interfaces)
+        }
+        Expr::Enum(name, variants) => format!("enum {} {{ {} }}", name, variants.iter()
+            .map(|(v, f)| if_else!(f.is_empty(), v.to_owned(), format!("{}({})", v, fields(f)))).collect::<Vec<_>>().join(", ")),
+        Expr::Interface(name, methods) => format!("interface {} {{ {} }}", name, methods.iter().map(|(m, t)| match t {
+            Type::Fun(args, output) => format!("fun {}({}): {}", m, args.iter().map(|a| a.print()).collect::<Vec<_>>().join(", "), output.print()),
+            _ => m.to_owned(),
+        }).collect::<Vec<_>>().join("; ")),
+        _ => value.print(),
+    }
+}
+
+// the right operand of && or || is only evaluated when the left one does not decide the result
+pub fn short_circuit(name: &str, args: &[Expr], scope: &Scope) -> Result<Expr, Exception> {
+    let operand = |expr: &Expr| match expr.eval(scope)? {
+        Bool(b) => Ok(b),
+        other => Err(Exception::UnexpectedArgumentType(if_else!(name == "and", "&&", "||").to_owned(), other.value_type().print())),
+    };
+    let left = operand(&args[0])?;
+    Ok(Bool(if_else!(left == (name == "or"), left, operand(&args[1])?)))
+}
+
+fn assert_equals(expected: &Expr, actual: &Expr) -> Result<Expr, Exception> {
+    if_else!(equals(expected, actual), Ok(Nil), Err(Exception::NotEqual(expected.print(), actual.print())))
+}
+
 fn run_while(cond: &Expr, body: &Vec<Expr>, scope: &mut Scope) -> Result<Expr, Exception> {
     let mut count = 0;
     let mut result = Ok(Nil);
     loop {
+        interrupt::check()?;
         count += 1;
         if count >= 1000000 {
             break Err(Exception::InfiniteLoop)
@@ -214,10 +565,43 @@ fn run_while(cond: &Expr, body: &Vec<Expr>, scope: &mut Scope) -> Result<Expr, E
 
 fn read_line() -> Result<Expr, Exception> {
     let mut line = String::new();
-    match io::stdin().read_line(&mut line) {
-        Err(_) => return Err(Exception::IOError),
-        _ => {}
-    }
+    if io::stdin().read_line(&mut line).is_err() { return Err(Exception::IOError) }
     Ok(Expr::Str(line))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_program;
+
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let resources = Resources::init("EN");
+        let mut scope = Scope::init();
+        let program = parse_program("## Increments\n## a number\nfun inc(x: Int): Int = x + 1\n## A point\nstruct Point(x: Float, y: Float)\nfun dec(x: Int): Int = x - 1");
+        program.unwrap().iter().for_each(|e| assert!(e.eval_mutable(&mut scope).is_ok()));
+        assert_eq!(Ok("inc: (Int)->Int\n  Increments\n  a number".to_owned()), describe("inc", &scope, &resources));
+        assert_eq!(Ok("struct Point(x: Float, y: Float)\n  A point".to_owned()), describe("Point", &scope, &resources));
+        assert_eq!(Ok("dec: (Int)->Int".to_owned()), describe("dec", &scope, &resources));
+        parse_program("## Decrements a decimal\nfun dec(x: Float): Float = x - 1.0").unwrap().iter().for_each(|e| assert!(e.eval_mutable(&mut scope).is_ok()));
+        assert_eq!(Ok("dec: (Int)->Int\ndec: (Float)->Float\n  Decrements a decimal".to_owned()), describe("dec", &scope, &resources));
+        assert_eq!(Ok("Str.trim: (Str)->Str\n  Removes the spaces at the beginning and the end of the string".to_owned()), describe("trim", &scope, &resources));
+        assert_eq!(Ok("while\n  Repeats a block while the condition is true: while (condition) { ... }".to_owned()), describe("while", &scope, &resources));
+        assert_eq!(Err(Exception::UndefinedSymbol("zz".to_owned())), describe("zz", &scope, &resources));
+        assert_eq!("\"inc: (Int)->Int\n  Increments\n  a number\"", scope.exec("help(inc)"));
+        assert_eq!("UndefinedSymbol(zz)", scope.exec("help(\"zz\")"));
+    }
+
+    #[test]
+    fn test_builtins_documented() {
+        let scope = Scope::init();
+        for lang in ["EN", "FR"] {
+            let resources = Resources::init(lang);
+            for name in scope.find_funs("") {
+                assert!(resources.get_doc(&name).is_some(), "{} is not documented in {}", name, lang);
+            }
+        }
+    }
+}
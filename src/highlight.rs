@@ -0,0 +1,174 @@
+use crate::expr::Expr::Fun;
+use crate::if_else;
+use crate::scope::Scope;
+use crate::types::Type;
+
+const KEYWORD: &str = "\x1b[35m";
+const LITERAL: &str = "\x1b[36m";
+const TYPE: &str = "\x1b[33m";
+const STRING: &str = "\x1b[32m";
+const COMMENT: &str = "\x1b[90m";
+const BRACKET: &str = "\x1b[1;34m";
+pub(crate) const HINT: &str = "\x1b[90m";
+pub(crate) const STD: &str = "\x1b[0m";
+
+const KEYWORDS: [&str; 14] = ["val", "var", "const", "fun", "struct", "enum", "interface", "test", "if", "else", "while", "match", "when", "is"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Keyword,
+    Literal,
+    Type,
+    Str,
+    Comment,
+    Bracket,
+    Other,
+}
+
+// the tokens of a line with their byte range, a string or a comment may not be closed yet
+fn tokens(line: &str) -> Vec<(usize, usize, Token)> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let at = |i: usize| chars.get(i).map(|c| c.0).unwrap_or(line.len());
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        let mut end = i + 1;
+        let token = match c {
+            '"' => {
+                let mut escaped = false;
+                while end < chars.len() && (escaped || chars[end].1 != '"') {
+                    escaped = !escaped && chars[end].1 == '\\';
+                    end += 1
+                }
+                end = (end + 1).min(chars.len());
+                Token::Str
+            }
+            '#' => { end = chars.len(); Token::Comment }
+            '(' | ')' | '[' | ']' | '{' | '}' => Token::Bracket,
+            _ if c.is_ascii_digit() => {
+                while end < chars.len() && (chars[end].1.is_ascii_digit() || chars[end].1 == '_'
+                    || chars[end].1 == '.' && chars.get(end + 1).is_some_and(|c| c.1.is_ascii_digit())) {
+                    end += 1
+                }
+                Token::Literal
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                while end < chars.len() && (chars[end].1.is_alphanumeric() || chars[end].1 == '_') {
+                    end += 1
+                }
+                let word = &line[at(i)..at(end)];
+                match word {
+                    _ if KEYWORDS.contains(&word) => Token::Keyword,
+                    "true" | "false" | "nil" => Token::Literal,
+                    _ if c.is_uppercase() => Token::Type,
+                    _ => Token::Other,
+                }
+            }
+            _ => Token::Other,
+        };
+        tokens.push((at(i), at(end), token));
+        i = end;
+    }
+    tokens
+}
+
+// byte positions of the bracket under or just before the cursor and of its matching bracket
+fn matching_brackets(tokens: &[(usize, usize, Token)], line: &str, pos: Option<usize>) -> Option<(usize, usize)> {
+    let pos = pos?;
+    let brackets: Vec<usize> = tokens.iter().filter(|t| t.2 == Token::Bracket).map(|t| t.0).collect();
+    let index = brackets.iter().position(|b| *b == pos)
+        .or_else(|| brackets.iter().position(|b| *b + 1 == pos))?;
+    let bracket = line.as_bytes()[brackets[index]];
+    let forward = b"([{".contains(&bracket);
+    let mut depth = 0;
+    let mut candidates: Box<dyn Iterator<Item = &usize>> = if_else!(forward, Box::new(brackets[index + 1..].iter()), Box::new(brackets[..index].iter().rev()));
+    candidates.find(|b| {
+        let opening = if_else!(forward, b"([{", b")]}").contains(&line.as_bytes()[**b]);
+        depth += if_else!(opening, 1, -1);
+        depth < 0
+    }).filter(|b| matches!((bracket, line.as_bytes()[**b]), (b'(', b')') | (b')', b'(') | (b'[', b']') | (b']', b'[') | (b'{', b'}') | (b'}', b'{')))
+        .map(|b| (brackets[index], *b))
+}
+
+// colors keywords, literals, types, strings and comments, and highlights the bracket matching the one at the cursor
+pub fn highlight(line: &str, cursor: Option<usize>) -> String {
+    let tokens = tokens(line);
+    let brackets = matching_brackets(&tokens, line, cursor);
+    let mut out = String::with_capacity(line.len() * 2);
+    for (start, end, token) in tokens {
+        let color = match token {
+            Token::Keyword => KEYWORD,
+            Token::Literal => LITERAL,
+            Token::Type => TYPE,
+            Token::Str => STRING,
+            Token::Comment => COMMENT,
+            Token::Bracket if brackets.is_some_and(|(a, b)| a == start || b == start) => BRACKET,
+            _ => "",
+        };
+        out += &if_else!(color.is_empty(), line[start..end].to_owned(), format!("{}{}{}", color, &line[start..end], STD));
+    }
+    out
+}
+
+// the rest of the signature of a function or a method when the line ends with its name and '('
+pub fn hint(scope: &Scope, line: &str) -> Option<String> {
+    let before = line.strip_suffix('(')?;
+    let start = before.rfind(|c: char| !c.is_alphanumeric() && c != '_').map(|p| p + 1).unwrap_or(0);
+    let name = &before[start..];
+    let (types, is_method) = match before[..start].strip_suffix('.') {
+        Some(receiver) => {
+            let receiver = &receiver[receiver_start(receiver)..];
+            let names = scope.read(receiver).infer_type(scope).ok()?.all_method_names(name, scope);
+            (names.iter().find_map(|n| scope.get(n)).map(|f| f.value_type()), true)
+        }
+        None if !name.is_empty() => (scope.find(name).filter(|f| matches!(f, Fun(..))).map(|f| f.value_type()), false),
+        None => (None, false),
+    };
+    match types? {
+        Type::Fun(args, output) => {
+            let args: Vec<String> = args.iter().skip(if_else!(is_method, 1, 0)).map(|t| t.print()).collect();
+            Some(format!("{})->{}", args.join(","), output.print()))
+        }
+        _ => None,
+    }
+}
+
+// start of the literal or the variable before a method call
+fn receiver_start(receiver: &str) -> usize {
+    match receiver.strip_suffix('"') {
+        Some(str) => str.rfind('"').unwrap_or(0),
+        None => receiver.rfind(|c: char| !c.is_alphanumeric() && c != '_' && c != '.').map(|p| p + 1).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!("\x1b[35mval\x1b[0m a: \x1b[33mInt\x1b[0m = \x1b[36m1_000\x1b[0m \x1b[90m# one\x1b[0m", highlight("val a: Int = 1_000 # one", None));
+        assert_eq!("print(\x1b[32m\"a(\\\"#\"\x1b[0m, \x1b[36mtrue\x1b[0m, \x1b[36m2.5\x1b[0m)", highlight("print(\"a(\\\"#\", true, 2.5)", None));
+        assert_eq!("\x1b[32m\"abc\x1b[0m", highlight("\"abc", Some(4)));
+        assert_eq!("f\x1b[1;34m(\x1b[0m[a], (b)\x1b[1;34m)\x1b[0m", highlight("f([a], (b))", Some(11)));
+        assert_eq!("f\x1b[1;34m(\x1b[0m[a], (b)\x1b[1;34m)\x1b[0m", highlight("f([a], (b))", Some(1)));
+        assert_eq!("f([a], (b)", highlight("f([a], (b)", Some(1)));
+        assert_eq!("f(]", highlight("f(]", Some(2)));
+        assert_eq!("f(a)", highlight("f(a)", None));
+    }
+
+    #[test]
+    fn test_hint() {
+        let mut scope = Scope::init();
+        scope.exec("fun inc(x: Int): Int = x + 1");
+        scope.exec("val s = \"abc\"");
+        assert_eq!(Some("Str)->Str!".to_owned()), hint(&scope, "val t = readFile("));
+        assert_eq!(Some("Int)->Int".to_owned()), hint(&scope, "inc("));
+        assert_eq!(Some(")->Str".to_owned()), hint(&scope, "s.trim("));
+        assert_eq!(Some(")->Str".to_owned()), hint(&scope, "print(\"a b\".trim("));
+        assert_eq!(None, hint(&scope, "inc(1"));
+        assert_eq!(None, hint(&scope, "s("));
+        assert_eq!(None, hint(&scope, "("));
+    }
+}
@@ -0,0 +1,125 @@
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use crate::exception::Exception;
+use crate::expr::Expr;
+use crate::expr::Expr::{Failure, Fun, Nil};
+use crate::functions::{Function, NativeFn};
+use crate::interrupt;
+use crate::interrupt::InterruptHandle;
+use crate::parser::parse_program;
+use crate::scope::Scope;
+use crate::types::Type;
+
+/// Interpreter for Rust applications embedding GroLang. Definitions are kept between evaluations.
+pub struct Interpreter {
+    scope: Scope,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self { Interpreter::new() }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter { Interpreter { scope: Scope::init(), interrupted: Arc::new(AtomicBool::new(false)) } }
+
+    /// Disables the file system functions.
+    pub fn set_sandbox(&mut self, sandbox: bool) { self.scope.set_sandbox(sandbox) }
+
+    /// Evaluates a source text and returns the value of its last statement.
+    /// The evaluation can be aborted from another thread with the handle of `interrupt_handle()`.
+    pub fn eval(&mut self, source: &str) -> Result<Expr, Exception> {
+        let program = parse_program(source).map_err(|e| Exception::CannotParse(e.message))?;
+        let scope = &mut self.scope;
+        let result = interrupt::run(&self.interrupted, || program.iter().try_fold(Nil, |_, expr| expr.eval_mutable(scope)));
+        match result? {
+            Failure(ex) => Err(ex),
+            result => Ok(result),
+        }
+    }
+
+    /// Returns a handle aborting the evaluations of this interpreter only.
+    pub fn interrupt_handle(&self) -> InterruptHandle { InterruptHandle(self.interrupted.clone()) }
+
+    /// Returns the value of a global variable.
+    pub fn get(&self, name: &str) -> Option<Expr> {
+        self.scope.get(name).filter(|e| !e.is_fun())
+    }
+
+    /// Defines or replaces a global variable, which is mutable by the Gro code.
+    pub fn set(&mut self, name: &str, value: impl Into<Expr>) {
+        self.scope.set(name, value.into(), Some(true))
+    }
+
+    /// Registers a Rust closure as a Gro function, with a signature like `(Int,Str)->Bool`.
+    /// Arguments are checked against the signature before the closure is called.
+    /// A function registered with other argument types than an existing one overloads it.
+    pub fn register<F>(&mut self, name: &str, signature: &str, fun: F) -> Result<(), Exception>
+        where F: Fn(&[Expr]) -> Result<Expr, Exception> + 'static {
+        match Type::from_str(signature)? {
+            types @ Type::Fun(..) => {
+                self.scope.add_fun(Fun(name.to_owned(), types, Function::Native(NativeFn(Rc::new(fun)))));
+                Ok(())
+            }
+            _ => Err(Exception::NotA("Fun".to_owned(), signature.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    #[test]
+    fn test_eval() {
+        let mut gro = Interpreter::new();
+        assert_eq!(Ok(Expr::Int(6)), gro.eval("val a = 2\nfun triple(x: Int): Int = x * 3 ; triple(a)"));
+        assert_eq!(Some(Expr::Int(2)), gro.get("a"));
+        assert_eq!(None, gro.get("triple"));
+        assert_eq!(Err(Exception::DivisionByZero), gro.eval("a / 0"));
+        assert!(matches!(gro.eval("1 +"), Err(Exception::CannotParse(_))));
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut gro = Interpreter::new();
+        gro.set("names", vec!("alice", "bob"));
+        gro.set("count", 1);
+        assert_eq!(Ok(Expr::Int(2)), gro.eval("count = count + 1"));
+        assert_eq!(Ok(2), i64::try_from(gro.get("count").unwrap()));
+        assert_eq!(Ok(vec!("alice".to_owned(), "bob".to_owned())), Vec::<String>::try_from(gro.get("names").unwrap()));
+    }
+
+    #[test]
+    fn test_interrupt_handle() {
+        let mut a = Interpreter::new();
+        let mut b = Interpreter::new();
+        let handle = a.interrupt_handle();
+        a.register("stop", "()->Int", move |_| { handle.interrupt(); Ok(Expr::Int(0)) }).unwrap();
+        b.eval("var n = 0").unwrap();
+        assert_eq!(Err(Exception::Interrupted), a.eval("stop() ; while (true) { 1 }"));
+        // the other interpreter and the next evaluations are not aborted
+        assert_eq!(Ok(Expr::Int(1)), b.eval("n = n + 1"));
+        assert_eq!(Ok(Expr::Int(2)), a.eval("1 + 1"));
+    }
+
+    #[test]
+    fn test_register() {
+        let mut gro = Interpreter::new();
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let log = calls.clone();
+        gro.register("log", "(Str)->Int", move |args| {
+            log.borrow_mut().push(String::try_from(args[0].clone())?);
+            Ok(Expr::from(log.borrow().len() as i64))
+        }).unwrap();
+        assert_eq!(Ok(Expr::Int(2)), gro.eval(r#"log("a") ; log("b")"#));
+        assert_eq!(vec!("a", "b"), *calls.borrow());
+        assert_eq!(Err(Exception::UnexpectedArgumentType("log".to_owned(), "Int".to_owned())), gro.eval("log(1)"));
+        assert!(gro.register("bad", "Int", |_| Ok(Nil)).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+#[cfg(feature = "repl")]
+use std::process;
+use std::sync::Arc;
+#[cfg(feature = "repl")]
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::exception::Exception;
+
+// flag of the evaluation running in the REPL, the only one aborted by Ctrl-C
+#[cfg(feature = "repl")]
+static REPL: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+thread_local! {
+    // flags of the evaluations running in this thread, an evaluation can start another one
+    static CURRENT: RefCell<Vec<Arc<AtomicBool>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Handle aborting the evaluations of an `Interpreter`, which can be sent to another thread.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(pub(crate) Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Aborts the running evaluation, which then fails with `Exception::Interrupted`.
+    pub fn interrupt(&self) { self.0.store(true, Ordering::SeqCst) }
+}
+
+// Ctrl-C aborts the running evaluation, or exits as usual when nothing is running
+#[cfg(feature = "repl")]
+pub fn install() {
+    ctrlc::set_handler(|| match REPL.lock().unwrap().as_ref() {
+        Some(flag) => flag.store(true, Ordering::SeqCst),
+        None => process::exit(130),
+    }).expect("Unable to install the Ctrl-C handler");
+}
+
+#[cfg(feature = "repl")]
+pub fn run_in_repl<T>(eval: impl FnOnce() -> T) -> T {
+    let flag = Arc::new(AtomicBool::new(false));
+    REPL.lock().unwrap().replace(flag.clone());
+    let result = run(&flag, eval);
+    REPL.lock().unwrap().take();
+    result
+}
+
+// the evaluation fails at its next check once the flag is set, the flag is cleared when it starts
+pub fn run<T>(flag: &Arc<AtomicBool>, eval: impl FnOnce() -> T) -> T {
+    flag.store(false, Ordering::SeqCst);
+    CURRENT.with(|c| c.borrow_mut().push(flag.clone()));
+    let result = eval();
+    CURRENT.with(|c| c.borrow_mut().pop());
+    result
+}
+
+pub fn check() -> Result<(), Exception> {
+    if CURRENT.with(|c| c.borrow().iter().any(|f| f.load(Ordering::SeqCst))) {
+        Err(Exception::Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scope::Scope;
+
+    use super::*;
+
+    #[test]
+    fn test_interrupt() {
+        let mut scope = Scope::init();
+        scope.exec("var a = 0");
+        let flag = Arc::new(AtomicBool::new(false));
+        let handle = InterruptHandle(flag.clone());
+        assert_eq!("Interrupted", run(&flag, || { handle.interrupt(); scope.exec("while (true) { a = a + 1 }") }));
+        assert_eq!("0", scope.exec("a"));
+        assert_eq!("1", scope.exec("a = a + 1"));
+        // a nested evaluation does not clear the flag of the outer one
+        let inner = Arc::new(AtomicBool::new(false));
+        assert_eq!("Interrupted", run(&flag, || { run(&inner, || ()); handle.interrupt(); run(&inner, || scope.exec("while (true) { a = a + 1 }")) }));
+        assert_eq!("1", run(&flag, || scope.exec("a")));
+    }
+}
@@ -0,0 +1,186 @@
+use pest::error::LineColLocation;
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::Parser;
+
+use crate::exception::Exception;
+use crate::expr::Expr;
+use crate::expr::Expr::{Bool, Failure, Float, Instance, Int, List, Map, Nil, Str, Variant};
+use crate::if_else;
+use crate::types::Type;
+
+#[derive(Parser)]
+#[grammar = "json.pest"]
+struct JsonParser;
+
+// returns a Try value: the parsed expression or a Failure with the position of the error
+pub fn parse_json(str: &str) -> Expr {
+    match JsonParser::parse(Rule::Json, str) {
+        Ok(mut pairs) => to_expr(pairs.next().unwrap()),
+        Err(e) => {
+            let (line, col) = match e.line_col {
+                LineColLocation::Pos(p) => p,
+                LineColLocation::Span(p, _) => p,
+            };
+            Failure(Exception::InvalidJson(format!("{}:{}", line, col), e.variant.message().to_string()))
+        }
+    }
+}
+
+fn to_expr(pair: Pair<Rule>) -> Expr {
+    match pair.as_rule() {
+        Rule::Object => {
+            let pairs: Vec<(Expr, Expr)> = pair.into_inner().map(|m| {
+                let mut inner = m.into_inner();
+                (to_expr(inner.next().unwrap()), to_expr(inner.next().unwrap()))
+            }).collect();
+            Map(Type::infer_map(&pairs), pairs)
+        }
+        Rule::Array => {
+            let values: Vec<Expr> = pair.into_inner().map(to_expr).collect();
+            List(Type::infer_list(&values), values)
+        }
+        Rule::String => Str(un_escape(pair.into_inner().as_str())),
+        Rule::Number => {
+            let str = pair.as_str();
+            str.parse::<i64>().map(Int).unwrap_or_else(|_| Float(str.parse::<f64>().unwrap()))
+        }
+        Rule::True => Bool(true),
+        Rule::False => Bool(false),
+        Rule::Null => Nil,
+        _ => panic!("unexpected JSON rule {:?}", pair.as_rule()),
+    }
+}
+
+fn un_escape(str: &str) -> String {
+    let mut result = String::with_capacity(str.len());
+    let mut chars = str.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('b') => result.push('\u{8}'),
+            Some('f') => result.push('\u{c}'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('u') => {
+                let mut code = read_hex(&mut chars);
+                // surrogate pairs are encoded as two consecutive escapes
+                if (0xD800..0xDC00).contains(&code) && chars.as_str().starts_with("\\u") {
+                    chars.nth(1);
+                    code = 0x10000 + ((code - 0xD800) << 10) + (read_hex(&mut chars) - 0xDC00);
+                }
+                result.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            Some(c) => result.push(c),
+            None => {}
+        }
+    }
+    result
+}
+
+fn read_hex(chars: &mut std::str::Chars) -> u32 {
+    u32::from_str_radix(&chars.take(4).collect::<String>(), 16).unwrap_or(0xFFFD)
+}
+
+pub fn to_json(expr: &Expr, pretty: bool) -> Result<String, Exception> {
+    let mut str = String::new();
+    write_json(expr, pretty, 0, &mut str)?;
+    Ok(str)
+}
+
+fn write_json(expr: &Expr, pretty: bool, level: usize, out: &mut String) -> Result<(), Exception> {
+    match expr {
+        Nil => out.push_str("null"),
+        Bool(x) => out.push_str(&x.to_string()),
+        Int(x) => out.push_str(&x.to_string()),
+        Float(x) if x.is_finite() => out.push_str(&expr.print()),
+        Float(_) => out.push_str("null"),
+        Str(x) => write_string(x, out),
+        List(_, vec) => write_collection(vec, pretty, level, "[", "]", out, |e, out| write_json(e, pretty, level + 1, out))?,
+        Map(_, vec) => write_collection(vec, pretty, level, "{", "}", out, |(k, v), out| {
+            write_string(&match k { Str(s) => s.clone(), _ => k.print() }, out);
+            out.push_str(if_else!(pretty, ": ", ":"));
+            write_json(v, pretty, level + 1, out)
+        })?,
+        // a variant without fields is written as its name
+        Variant(_, name, vec) if vec.is_empty() => write_string(name, out),
+        Instance(_, vec) | Variant(_, _, vec) => write_collection(vec, pretty, level, "{", "}", out, |(k, v), out| {
+            write_string(k, out);
+            out.push_str(if_else!(pretty, ": ", ":"));
+            write_json(v, pretty, level + 1, out)
+        })?,
+        _ => return Err(Exception::NotA("JSON".to_owned(), expr.print())),
+    }
+    Ok(())
+}
+
+fn write_collection<T>(vec: &[T], pretty: bool, level: usize, prefix: &str, suffix: &str, out: &mut String,
+                       write: impl Fn(&T, &mut String) -> Result<(), Exception>) -> Result<(), Exception> {
+    out.push_str(prefix);
+    for (i, e) in vec.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if pretty {
+            out.push('\n');
+            out.push_str(&"  ".repeat(level + 1));
+        }
+        write(e, out)?;
+    }
+    if pretty && !vec.is_empty() {
+        out.push('\n');
+        out.push_str(&"  ".repeat(level));
+    }
+    out.push_str(suffix);
+    Ok(())
+}
+
+fn write_string(str: &str, out: &mut String) {
+    out.push('"');
+    for c in str.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scope::Scope;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        assert_eq!("{\"a\":[1,2.5,nil],\"b\":{\"c\":true}}", parse_json(" {\"a\": [1, 2.5, null],\n \"b\": {\"c\": true}} ").print());
+        assert_eq!(Str("tab\tquote\"\u{e9}\u{1F600}".to_owned()), parse_json(r#""tab\tquote\"\u00e9\ud83d\ude00""#));
+        assert_eq!("-12000.0", parse_json("-1.2e4").print());
+        assert_eq!("InvalidJson(1:9, expected String)", parse_json("{\"a\": 1,}").print());
+        assert_eq!("InvalidJson(2:1, expected Object, Array, String, Number, True, False, or Null)", parse_json("[1,\n]").print());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut scope = Scope::init();
+        scope.set("json", Str(r#"{"name": "bob", "notes": [12, 15.5], "email": null}"#.to_owned()), None);
+        scope.exec("val m = parseJson(json)");
+        assert_eq!(r#""{"name":"bob","notes":[12,15.5],"email":null}""#, scope.exec("m.toJson(false)"));
+        assert_eq!(Ok("{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {}\n}".to_owned()), to_json(&parse_json(r#"{"a":[1,2],"b":{}}"#), true));
+        scope.exec("struct Point(x: Float, y: Float)");
+        assert_eq!(r#""{"x":1.0,"y":2.5}""#, scope.exec("toJson(Point(1.0, 2.5), false)"));
+        scope.exec("enum Shape { Dot, Circle(r: Float) }");
+        assert_eq!(r#""["Dot",{"r":1.0}]""#, scope.exec("toJson([Dot, Circle(1.0)], false)"));
+        assert_eq!("true", scope.exec(r#"parseJson("[1,").isFailure()"#));
+    }
+}
@@ -1,10 +1,19 @@
-use std::collections::VecDeque;
-
-use dialoguer::{Input, theme::ColorfulTheme};
-use sys_locale::get_locale;
-
 use crate::scope::Scope;
-use crate::utils::Resources;
+use crate::parser::parse_warnings;
+use crate::utils::{language, Resources};
+
+pub use crate::exception::Exception;
+pub use crate::expr::Expr;
+pub use crate::format::{format_files, format_source};
+pub use crate::functions::Function;
+pub use crate::interpreter::Interpreter;
+pub use crate::interrupt::InterruptHandle;
+#[cfg(feature = "lsp")]
+pub use crate::lsp::lsp_server;
+#[cfg(feature = "repl")]
+pub use crate::repl::repl;
+pub use crate::testing::run_tests;
+pub use crate::types::Type;
 
 mod parser;
 mod types;
@@ -13,6 +22,20 @@ mod functions;
 mod expr;
 mod scope;
 mod utils;
+mod interrupt;
+mod files;
+mod json;
+mod patterns;
+mod convert;
+mod interpreter;
+mod format;
+mod testing;
+#[cfg(feature = "repl")]
+mod repl;
+#[cfg(feature = "repl")]
+mod highlight;
+#[cfg(feature = "lsp")]
+mod lsp;
 
 #[macro_export]
 macro_rules! if_else {
@@ -21,96 +44,30 @@ macro_rules! if_else {
 
 pub const LANG: &str = "GroLang";
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
-const RED: &str = "\x1b[1;31m";
-const BLUE: &str = "\x1b[1;34m";
-const STD: &str = "\x1b[0m";
-
-
-
-#[derive(Debug, Default)]
-pub struct History {
-    deque: VecDeque<String>,
-}
-impl History {
-    fn print(&self) {
-        self.deque.iter().rev().for_each(|e| println!("# {}", e))
-    }
-    fn load(&self, _filename: &str) {
-        // TODO
-    }
-    fn save(&self, _filename: &str) {
-        // TODO
-    }
-    fn drop_last(&mut self) {
-        self.deque.pop_front()
-        ;
-    }
 
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub sandbox: bool,
+    // colored output and syntax highlighting in the REPL
+    pub color: bool,
 }
 
-impl<T: ToString> dialoguer::History<T> for History {
-    fn read(&self, pos: usize) -> Option<String> {
-        self.deque.get(pos).cloned()
-    }
-
-    fn write(&mut self, val: &T) {
-        let val = val.to_string();
-        if !val.starts_with(":") {
-            self.deque.push_front(val);
-        }
+impl Options {
+    pub(crate) fn new_scope(&self) -> Scope {
+        let mut scope = Scope::init();
+        scope.set_sandbox(self.sandbox);
+        scope
     }
 }
 
-pub fn eval_line(line: &str) {
-    let mut scope = Scope::init();
+pub fn eval_line(line: &str, options: &Options) {
+    let mut scope = options.new_scope();
+    let resources = Resources::init(&language());
+    parse_warnings(line, &scope.enums()).iter().for_each(|w| eprintln!("{}", w.format(&resources)));
     let result = scope.exec(line);
     println!("{}", result)
 }
 
-pub fn repl() {
-    let mut debug = false;
-    let locale = get_locale().unwrap_or_else(|| String::from("FR"));
-    let resources = Resources::init(&locale[0..2].to_uppercase());
-    println!("{BLUE}{LANG} Version {VERSION}{STD}\n{}\n", resources.help.split("\n").next().unwrap());
-    let mut scope = Scope::init();
-    let mut history = History::default();
-    loop {
-        let input = Input::<String>::with_theme(&ColorfulTheme::default())
-            .completion_with(&scope)
-            .with_prompt("gro")
-            .history_with(&mut history)
-            .interact_text().expect("Unable to read stdin");
-        if input.starts_with('#') {
-            history.drop_last();
-            continue
-        }
-        if input.starts_with(':') {
-            let v: Vec<&str> = input.split(" ").collect();
-            match input[1..2].to_string().as_str() {
-                "q" => break,
-                "d" => { debug = !debug; println!("# debug={}", debug) },
-                "h" => history.print(),
-                "l" if v.len() == 2 => history.load(v[1]),
-                "s" if v.len() == 2 => history.save(v[1]),
-                _ => println!("{}", resources.help),
-            }
-            continue;
-        }
-        let expr = scope.read(&input);
-        if expr.is_failure() {
-            println!("{RED}{} {STD}", expr.to_exception().format(&resources));
-            continue;
-        }
-        let result = expr.eval_or_failed(&mut scope);
-        if expr.is_failure() {
-            println!("{RED}{} {STD}", expr.to_exception().format(&resources));
-        } else {
-            println!("{}", result.print())
-        }
-    }
-}
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -146,6 +103,11 @@ mod tests {
         assert_eq!("i", scope.exec("val i = 0"));
         assert_eq!("NotMutable(i)", scope.exec("i = 1"));
         assert_eq!("NotDefined(z)", scope.exec("z = 0"));
+        assert_eq!("n", scope.exec("var n: Number = 1"));
+        assert_eq!("2.5", scope.exec("n = 2.5"));
+        assert_eq!("UnexpectedType(Str)", scope.exec("n = \"a\""));
+        assert_eq!("o", scope.exec("var o: Int? = nil"));
+        assert_eq!("3", scope.exec("o = 3"));
 
     }
 
@@ -154,6 +116,21 @@ mod tests {
         let mut scope = Scope::init();
         scope.exec("val l = [1,2,3]");
         scope.exec(r#"val m = {"a":1, "b":1}"#);
+        let infer = |scope: &Scope, str: &str| scope.read(str).infer_type(scope).map(|t| t.print());
+        assert_eq!("n", scope.exec("var n: List<Number> = [1, 2]"));
+        assert_eq!(Ok("List<Number>".to_owned()), infer(&scope, "n"));
+        assert_eq!("[1.5]", scope.exec("n = [1.5]"));
+        assert_eq!("UnexpectedType(List<Str>)", scope.exec("n = [\"a\"]"));
+        assert_eq!("o", scope.exec("val o: List<Int?> = [1, nil]"));
+        assert_eq!(Ok("List<Int?>".to_owned()), infer(&scope, "o"));
+        assert_eq!("UnexpectedType(List<Any>)", scope.exec("val p: List<Int> = [1, nil]"));
+        assert_eq!("q", scope.exec(r#"val q: Map<Str, List<Number>> = {"a": [1], "b": []}"#));
+        assert_eq!(Ok("Map<Str,List<Number>>".to_owned()), infer(&scope, "q"));
+        assert_eq!("UnexpectedType(List<Int>)", scope.exec("val r: List<Str> = l"));
+        // the elements of a collection are evaluated
+        assert_eq!("[2,3.5]", scope.exec("[1 + 1, 1 + 2.5]"));
+        assert_eq!(r#"{"a":[1,2,3],"b":2}"#, scope.exec(r#"{"a": l, "b": 1 + 1}"#));
+        assert_eq!(Ok("List<Number>".to_owned()), infer(&scope, "[1, 2.5 * 2]"));
     }
 
     #[test]
@@ -182,8 +159,66 @@ mod tests {
         assert_eq!("true", scope.exec("a == 1 && b == 2"));
         assert_eq!("false", scope.exec("a == 1 && b == 1"));
         assert_eq!("false", scope.exec("a == 2 && b == 2"));
+        // the right operand is not evaluated when the left one decides the result
+        assert_eq!("false", scope.exec("a == 2 && 10 / (a - 1) > 1"));
+        assert_eq!("true", scope.exec("a == 1 || 10 / (a - 1) > 1"));
+        assert_eq!("DivisionByZero", scope.exec("a == 1 && 10 / (a - 1) > 1"));
+        assert_eq!("UnexpectedArgumentType(&&, Int)", scope.exec("a && true"));
+        assert_eq!("UnexpectedArgumentType(||, Str)", scope.exec("false || \"a\""));
+        assert_eq!(Ok(Type::Bool), scope.read("a == 1 || b == 1").infer_type(&scope));
         assert_eq!("true", scope.exec("a < b"));
         assert_eq!("false", scope.exec("a >= b"));
+        assert_eq!("true", scope.exec("a.add(1) == b"));
+        // the equality compares the elements of the collections and the structures
+        scope.exec("struct Point(x: Int, y: Int)");
+        assert_eq!("true", scope.exec("\"ab\" == \"ab\" && true != false"));
+        assert_eq!("true", scope.exec("[1, 2] == [1.0, 2.0]"));
+        assert_eq!("false", scope.exec("[1, 2] == [1, 2, 3]"));
+        assert_eq!("true", scope.exec("{\"a\": [1], \"b\": [2]} == {\"b\": [2], \"a\": [1]}"));
+        assert_eq!("true", scope.exec("Point(1, 2) == Point(1, 2) && Point(1, 2) != Point(2, 1)"));
+        assert_eq!("false", scope.exec("a == nil"));
+        // the strings, booleans and lists are ordered
+        assert_eq!("true", scope.exec("\"abc\" < \"abd\" && \"b\" > \"abc\""));
+        assert_eq!("true", scope.exec("false < true"));
+        assert_eq!("true", scope.exec("[1, 2] < [1, 3] && [1] < [1, 0] && [2] >= [1, 5]"));
+        assert_eq!("CannotCompare(Point, Point)", scope.exec("Point(1, 2) < Point(2, 1)"));
+        assert_eq!("CannotCompare(Str, Int)", scope.exec("\"a\" <= 1"));
+        assert_eq!("[1,2,3]", scope.exec("[3, 1, 2].sorted()"));
+        assert_eq!("[\"a\",\"ab\",\"b\"]", scope.exec("[\"b\", \"ab\", \"a\"].sorted()"));
+        assert_eq!("[[1],[1,0],[2]]", scope.exec("[[2], [1, 0], [1]].sorted()"));
+        assert_eq!("1", scope.exec("[3, 1, 2].min()"));
+        assert_eq!("\"b\"", scope.exec("[\"b\", \"ab\"].max()"));
+        assert_eq!("nil", scope.exec("[].max()"));
+        assert_eq!("CannotCompare(Point, Point)", scope.exec("[Point(1, 2), Point(0, 0)].sorted()"));
+        assert_eq!("CannotCompare(Int, Str)", scope.exec("[2, \"a\", 1].sorted()"));
+        assert_eq!("[1,2.0,2,3]", scope.exec("[3, 2.0, 1, 2].sorted()"));
+        assert_eq!(Ok("Int?".to_owned()), scope.read("[3, 1].min()").infer_type(&scope).map(|t| t.print()));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut scope = Scope::init();
+        assert_eq!("42", scope.exec("\" 42 \".toInt()"));
+        assert_eq!("CannotConvert(4x, Int)", scope.exec("\"4x\".toInt()"));
+        assert_eq!("0", scope.exec("toInt(\"\").getOrElse(0)"));
+        assert_eq!("true", scope.exec("\"1.5e3\".toFloat() == 1500"));
+        assert_eq!("true", scope.exec("\"nan\".toFloat().isFailure()"));
+        assert_eq!("false", scope.exec("\"false\".toBool()"));
+        assert_eq!("true", scope.exec("\"yes\".toBool().isFailure()"));
+        assert_eq!("\"abc\"", scope.exec("\"abc\".toStr()"));
+        assert_eq!("\"[1,2]\"", scope.exec("[1, 2].toStr()"));
+        assert_eq!("3.0", scope.exec("3.toFloat()"));
+        assert_eq!("-2", scope.exec("(-2.7).toInt()"));
+        assert_eq!("-3", scope.exec("(-2.7).toInt(\"floor\")"));
+        assert_eq!("3", scope.exec("2.1.toInt(\"ceil\")"));
+        assert_eq!("3", scope.exec("2.5.toInt(\"round\")"));
+        assert_eq!("UnknownRoundingMode(up)", scope.exec("2.5.toInt(\"up\")"));
+        scope.exec("val big: Float = \"1e19\".toFloat()");
+        assert_eq!("CannotConvert(10000000000000000000, Int)", scope.exec("big.toInt()"));
+        // a failed parsing cannot be assigned to a variable of the expected type
+        assert_eq!("CannotConvert(a, Int)", scope.exec("val n: Int = \"a\".toInt()"));
+        assert_eq!("n", scope.exec("val n: Int = \"7\".toInt()"));
+        assert_eq!(Ok(Type::Try(Box::new(Type::Int))), scope.read("\"7\".toInt()").infer_type(&scope));
     }
 
     #[test]
@@ -197,6 +232,273 @@ mod tests {
         assert_eq!("nil", scope.exec("if (false) 1"));
     }
 
+    #[test]
+    fn test_struct() {
+        let mut scope = Scope::init();
+        assert_eq!("Point", scope.exec("struct Point(x: Float, y: Float)"));
+        assert_eq!("p", scope.exec("val p: Point = Point(1.0, 2.0)"));
+        assert_eq!("Point(x=1.0,y=2.0)", scope.exec("p"));
+        assert_eq!("WrongArgumentsNumber(Point, 2, 1)", scope.exec("Point(1.0)"));
+        assert_eq!("UnexpectedArgumentType(Point, Int)", scope.exec("Point(1, 2.0)"));
+    }
+
+    #[test]
+    fn test_enums() {
+        let mut scope = Scope::init();
+        assert_eq!("Color", scope.exec("enum Color { Red, Green, Blue }"));
+        assert_eq!("Shape", scope.exec("enum Shape { Circle(r: Float), Rect(w: Float, h: Float) }"));
+        assert_eq!("c", scope.exec("val c: Color = Green"));
+        assert_eq!("Green", scope.exec("c"));
+        assert_eq!("Red", scope.exec("Color.Red"));
+        assert_eq!("Circle(r=1.0)", scope.exec("Circle(1.0)"));
+        assert_eq!("Rect(w=1.0,h=2.0)", scope.exec("Shape.Rect(1.0, 2.0)"));
+        assert_eq!("2.0", scope.exec("Rect(1.0, 2.0).h"));
+        assert_eq!("UnexpectedArgumentType(Circle, Int)", scope.exec("Circle(1)"));
+        assert_eq!("UnexpectedType(Color)", scope.exec("val s: Shape = Red"));
+        assert_eq!("true", scope.exec("c == Color.Green"));
+        assert_eq!("true", scope.exec("Circle(1.0) != Circle(2.0)"));
+        assert_eq!("true", scope.exec("Red < c && Blue >= c"));
+        assert_eq!("[Red,Green,Blue]", scope.exec("[Blue, Red, c].sorted()"));
+        assert_eq!("CannotCompare(Color, Shape)", scope.exec("Red < Circle(1.0)"));
+        assert_eq!("[Red,Blue]", scope.exec("[Red, Blue]"));
+        assert_eq!("\"green\"", scope.exec("{Red: \"red\", Green: \"green\"}.get(c)"));
+        assert_eq!("AlreadyDefined(Color)", scope.exec("enum Color { Cyan }"));
+        assert_eq!(vec!(vec!("Red", "Green", "Blue"), vec!("Circle", "Rect")), scope.enums());
+        // the variants declared by several enums are qualified by their enum
+        assert_eq!("Light", scope.exec("enum Light { Red, Amber, Green }"));
+        assert_eq!("Amber", scope.exec("Amber"));
+        assert_eq!("AmbiguousVariant(Red, Color, Light)", scope.exec("Red"));
+        assert_eq!("true", scope.exec("Light.Red != Color.Red"));
+        assert_eq!("UnexpectedType(Light)", scope.exec("val d: Color = Light.Green"));
+        assert_eq!("\"red\"", scope.exec("match (Light.Red) { Green -> \"green\"; Red -> \"red\"; else -> \"amber\" }"));
+        assert_eq!("Print", scope.exec("enum Print { print }"));
+        assert_eq!("print", scope.exec("Print.print"));
+        assert!(scope.forget("Light"));
+        assert_eq!("Red", scope.exec("Red"));
+        let infer = |str: &str| scope.read(str).infer_type(&scope).map(|t| t.print());
+        assert_eq!(Ok("Shape".to_owned()), infer("Shape.Circle(2.0)"));
+        assert_eq!(Ok("List<Color>".to_owned()), infer("[c, Blue]"));
+    }
+
+    #[test]
+    fn test_match() {
+        let mut scope = Scope::init();
+        scope.exec("struct Point(x: Float, y: Float)");
+        scope.exec("enum Shape { Circle(r: Float), Rect(w: Float, h: Float) }");
+        scope.exec(r#"fun kind(v: Any): Str = match (v) {
+          0 -> "zero"
+          1..9 -> "small"
+          is Int -> "int"
+          [a, _] -> a
+          {"name": n} -> n
+          Point(x, 0.0) -> "on the axis"
+          Shape.Circle(r) if r > 1.0 -> "big circle"
+          Circle(_) -> "circle"
+          else -> "other"
+        }"#);
+        for (value, expected) in [("0", "zero"), ("5", "small"), ("50", "int"), (r#"["a", "b"]"#, "a"), (r#"{"name": "bob", "age": 3}"#, "bob"),
+                                  ("Point(1.0, 0.0)", "on the axis"), ("Point(1.0, 2.0)", "other"), ("Circle(2.0)", "big circle"), ("Circle(1.0)", "circle"), ("nil", "other")] {
+            assert_eq!(format!("\"{}\"", expected), scope.exec(&format!("kind({})", value)), "{}", value);
+        }
+        assert_eq!("4", scope.exec("when (3) { x -> x + 1 }"));
+        assert_eq!("x", scope.exec("val x = match (Rect(1.0, 2.0)) { Rect(w, h) -> w * h; Circle(r) -> r }"));
+        assert_eq!("2.0", scope.exec("x"));
+        assert_eq!("NoMatchingCase(2)", scope.exec("match (2) { 1 -> true }"));
+        // the numbers are matched by value
+        assert_eq!("1", scope.exec("match (1.0) { 1 -> 1; else -> 0 }"));
+        assert_eq!("\"a\"", scope.exec("{1: \"a\"}.get(1.0)"));
+        assert_eq!("DuplicateBinding(a)", scope.exec("match ([1, 2]) { [a, a] -> a; else -> 0 }"));
+        assert_eq!("DuplicateBinding(r)", scope.exec("match (0) { 0 -> 0; {\"k\": r, \"v\": [r]} -> r }"));
+        // the bound variables are not visible after the match
+        assert_eq!("UndefinedSymbol(w)", scope.exec("w"));
+        let infer = |str: &str| scope.read(str).infer_type(&scope).map(|t| t.print());
+        assert_eq!(Ok("Str".to_owned()), infer("match (1) { 1 -> \"a\"; else -> \"b\" }"));
+        assert_eq!(Ok("Any".to_owned()), infer("match (1) { 1 -> \"a\"; n -> n }"));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut scope = Scope::init();
+        scope.exec("var i = 0");
+        scope.exec("var sum = 0");
+        assert_eq!("3", scope.exec("while (i < 3) { val sq = i * i; sum = sum + sq; i = i + 1 }"));
+        assert_eq!("5", scope.exec("sum"));
+        assert_eq!("UndefinedSymbol(sq)", scope.exec("sq"));
+        assert_eq!("1", scope.exec("if (true) { val tmp = 1; tmp } else 0"));
+        assert_eq!("UndefinedSymbol(tmp)", scope.exec("tmp"));
+        // an inner declaration shadows the outer one until the end of its block
+        scope.exec("var x = 1");
+        assert_eq!("3", scope.exec("if (true) { var x = 2; x = 3; x }"));
+        assert_eq!("1", scope.exec("x"));
+        assert_eq!("AlreadyDefined(x)", scope.exec("{ val x = 2; val x = 3 }"));
+        assert_eq!("4", scope.exec("if (true) { x = 4 }"));
+        assert_eq!("4", scope.exec("x"));
+        scope.exec("fun f(x: Int): Int = { val x = 2; x }");
+        assert_eq!("2", scope.exec("f(1)"));
+        // the methods declared in a block are global
+        scope.exec("if (true) { fun Str.shout(): Str = this.trim() }");
+        assert_eq!("\"a\"", scope.exec("\" a \".shout()"));
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut scope = Scope::init();
+        scope.exec("fun counter(): () -> Int = { var n = 0; fun next(): Int = { n = n + 1; n }; next }");
+        scope.exec("val c = counter()");
+        scope.exec("val d = counter()");
+        assert_eq!("1", scope.exec("c()"));
+        assert_eq!("2", scope.exec("c()"));
+        assert_eq!("1", scope.exec("d()"));
+        scope.exec("fun adder(k: Int): (Int) -> Int = { fun plus(x: Int): Int = x + k; plus }");
+        scope.exec("fun twice(f: (Int) -> Int): (Int) -> Int = { fun g(x: Int): Int = f(f(x)); g }");
+        assert_eq!("t", scope.exec("val t = twice(adder(3))"));
+        assert_eq!("7", scope.exec("t(1)"));
+        // the variables are those of the definition, not of the caller
+        scope.exec("val x = 1");
+        scope.exec("fun getX(): Int = x");
+        scope.exec("fun shadow(): Int = { val x = 2; getX() }");
+        assert_eq!("1", scope.exec("shadow()"));
+        // a method declared in a block closes over it
+        assert_eq!("2", scope.exec(r#"{ val k = 2; fun Str.m(): Int = k; "a".m() }"#));
+        assert_eq!("2", scope.exec(r#""b".m()"#));
+        scope.exec("fun getY(): Int = y");
+        scope.exec("fun caller(): Int = { val y = 2; getY() }");
+        assert_eq!("UndefinedSymbol(y)", scope.exec("caller()"));
+        // a function can change a global variable
+        scope.exec("var total = 0");
+        scope.exec("fun addTotal(v: Int): Int = { total = total + v; total }");
+        scope.exec("addTotal(2)");
+        assert_eq!("5", scope.exec("addTotal(3)"));
+        assert_eq!("5", scope.exec("total"));
+        scope.exec("fun setX(): Int = { x = 2 }");
+        assert_eq!("NotMutable(x)", scope.exec("setX()"));
+    }
+
+    #[test]
+    fn test_interfaces() {
+        let mut scope = Scope::init();
+        assert_eq!("Shape", scope.exec("interface Shape { fun area(): Float; fun scale(k: Float): Shape }"));
+        assert_eq!("Circle", scope.exec(r#"struct Circle(r: Float) : Shape {
+          fun area(): Float = 3.0 * this.r * this.r
+          fun scale(k: Float): Circle = Circle(this.r * k)
+        }"#));
+        scope.exec("fun Square.area(): Float = this.side * this.side");
+        scope.exec("fun Square.scale(k: Float): Shape = Square(this.side * k)");
+        assert_eq!("Square", scope.exec("struct Square(side: Float) : Shape"));
+        assert_eq!("NotImplemented(Rect, Shape.scale)", scope.exec("struct Rect(w: Float) : Shape { fun area(): Float = this.w }"));
+        assert_eq!("NotImplemented(Line, Shape.area)", scope.exec("struct Line(l: Float) : Shape { fun area(): Int = 0; fun scale(k: Float): Line = this }"));
+        assert_eq!("NotA(Interface, Square)", scope.exec("struct Cube(side: Float) : Square"));
+        assert_eq!("size", scope.exec("fun size(s: Shape): Float = s.scale(2.0).area()"));
+        assert_eq!("12.0", scope.exec("size(Circle(1.0))"));
+        assert_eq!("4.0", scope.exec("size(Square(1.0))"));
+        assert_eq!("shapes", scope.exec("val shapes: List<Shape> = [Circle(1.0), Square(2.0)]"));
+        assert_eq!("7.0", scope.exec("match (shapes) { [c, s] -> c.area() + s.area() }"));
+        assert_eq!("UnexpectedArgumentType(size, Int)", scope.exec("size(1)"));
+        let infer = |str: &str| scope.read(str).infer_type(&scope).map(|t| t.print());
+        assert_eq!(Ok("Float".to_owned()), infer("Square(1.0).scale(2.0).area()"));
+        // a failed declaration leaves no struct behind
+        assert_eq!("UndefinedMethod(Rect)", scope.exec("Rect(1.0)"));
+        // each scope keeps its own implementations
+        let mut other = Scope::init();
+        other.exec("interface Shape { fun area(): Float }");
+        assert_eq!("Circle", other.exec("struct Circle(r: Float)"));
+        assert_eq!("12.0", scope.exec("size(Circle(1.0))"));
+        other.exec("fun size(s: Shape): Float = s.area()");
+        assert_eq!("UnexpectedArgumentType(size, Circle)", other.exec("size(Circle(1.0))"));
+    }
+
+    #[test]
+    fn test_generics() {
+        let mut scope = Scope::init();
+        assert_eq!("Pair", scope.exec("struct Pair<A, B>(a: A, b: B)"));
+        assert_eq!("p", scope.exec("val p: Pair<Int, Str> = Pair(1, \"a\")"));
+        assert_eq!("Pair(a=1,b=\"a\")", scope.exec("p"));
+        assert_eq!("UnexpectedType(Pair<Str,Str>)", scope.exec("val q: Pair<Int, Str> = Pair(\"b\", \"a\")"));
+        scope.exec("fun same<T>(x: T, y: T): T = y");
+        scope.exec("fun flip<A, B>(a: A, b: B): Pair<B, A> = Pair(b, a)");
+        scope.exec("fun index<A, B>(p: Pair<A, B>): Map<A, B> = {}");
+        scope.exec("fun wrap<T>(x: T): List<T> = []");
+        assert_eq!("2.0", scope.exec("same(1, 2.0)"));
+        assert_eq!("UnexpectedArgumentType(same, Str)", scope.exec("same(1, \"a\")"));
+        assert_eq!("Pair(a=\"a\",b=1)", scope.exec("flip(1, \"a\")"));
+        assert_eq!("UnexpectedArgumentType(index, List<Int>)", scope.exec("index([1])"));
+        let infer = |str: &str| scope.read(str).infer_type(&scope).map(|t| t.print());
+        assert_eq!(Ok("Number".to_owned()), infer("same(1, 2.0)"));
+        assert_eq!(Ok("Pair<Str,Int>".to_owned()), infer("flip(1, \"a\")"));
+        assert_eq!(Ok("Map<Int,Str>".to_owned()), infer("index(p)"));
+        assert_eq!(Ok("List<Bool>".to_owned()), infer("wrap(true)"));
+        assert_eq!(Ok("Pair<Float,List<Int>>".to_owned()), infer("Pair(1.0, [1])"));
+    }
+
+    #[test]
+    fn test_overloading() {
+        let mut scope = Scope::init();
+        scope.exec("struct Circle(r: Float)");
+        scope.exec("struct Rect(w: Float, h: Float)");
+        assert_eq!("area", scope.exec("fun area(c: Circle): Float = 3.0"));
+        assert_eq!("area", scope.exec("fun area(r: Rect): Float = 2.0"));
+        assert_eq!("AlreadyDefined(area)", scope.exec("fun area(c: Circle): Int = 1"));
+        assert_eq!("3.0", scope.exec("area(Circle(1.0))"));
+        assert_eq!("2.0", scope.exec("area(Rect(1.0, 2.0))"));
+        assert_eq!("NoMatchingOverload(area, area(Circle)->Float, area(Rect)->Float)", scope.exec("area(1)"));
+        scope.exec("fun show(x: Int): Str = \"int\"");
+        scope.exec("fun show(x: Number): Str = \"number\"");
+        scope.exec("fun show(x: Any): Str = \"any\"");
+        assert_eq!("\"int\"", scope.exec("show(1)"));
+        assert_eq!("\"number\"", scope.exec("show(1.0)"));
+        assert_eq!("\"any\"", scope.exec("show(true)"));
+        scope.exec("fun mix(x: Int, y: Number): Int = 1");
+        scope.exec("fun mix(x: Number, y: Int): Int = 2");
+        assert_eq!("AmbiguousCall(mix, mix(Int,Number)->Int, mix(Number,Int)->Int)", scope.exec("mix(1, 1)"));
+        assert_eq!("2", scope.exec("mix(1.0, 1)"));
+        assert_eq!("\"int\"", scope.exec("1.show()"));
+        let infer = |str: &str| scope.read(str).infer_type(&scope).map(|t| t.print());
+        assert_eq!(Ok("Float".to_owned()), infer("area(Rect(1.0, 2.0))"));
+        assert_eq!(Ok("Str".to_owned()), infer("show(1)"));
+        // the functions do not redefine the operators
+        scope.exec("fun add(x: Int): Int = x");
+        assert_eq!("3", scope.exec("1 + 2"));
+        assert_eq!("4.0", scope.exec("1.5 + 2.5"));
+        assert_eq!("5", scope.exec("add(5)"));
+        scope.exec("fun add(a: Int, b: Int): Int = a + b + 1");
+        assert_eq!("3", scope.exec("add(1, 2)"));
+        assert_eq!("3", scope.exec("{ fun sub(a: Int, b: Int): Int = 0; 1 + 2 }"));
+        // the functions shadow the other methods of the receiver
+        scope.exec("fun toStr(x: Int): Str = \"int\"");
+        assert_eq!("\"int\"", scope.exec("1.toStr()"));
+        assert_eq!("\"1.0\"", scope.exec("1.0.toStr()"));
+    }
+
+    #[test]
+    fn test_methods() {
+        let mut scope = Scope::init();
+        scope.exec("struct Point(x: Float, y: Float)");
+        scope.exec("val p = Point(3.0, 4.0)");
+        assert_eq!("3.0", scope.exec("p.x"));
+        // a field is only read with a '.', and a method is always called with parentheses
+        assert_eq!("UndefinedMethod(y)", scope.exec("y(p)"));
+        assert_eq!("UndefinedField(z, Point)", scope.exec("p.z"));
+        assert_eq!("UndefinedField(trim, Str)", scope.exec("\" a\".trim"));
+        assert_eq!("Point.norm", scope.exec("fun Point.norm(): Float = this.x * this.x + this.y * this.y"));
+        assert_eq!("25.0", scope.exec("p.norm()"));
+        assert_eq!("Str.shout", scope.exec("fun Str.shout(n: Int): Str = this.trim()"));
+        assert_eq!("\"hi\"", scope.exec("\" hi \".shout(1)"));
+        assert_eq!("UnexpectedArgumentType(Str.shout, Float)", scope.exec("\"hi\".shout(1.0)"));
+        assert_eq!("Number.half", scope.exec("fun Number.half(): Number = this / 2"));
+        assert_eq!("2", scope.exec("4.half()"));
+        assert_eq!("AlreadyDefined(Point.norm)", scope.exec("fun Point.norm(): Float = 0.0"));
+        assert_eq!("UndefinedMethod(norm)", scope.exec("1.norm()"));
+        scope.exec("struct Pair<A, B>(a: A, b: B)");
+        assert_eq!("Pair.first", scope.exec("fun Pair<A, B>.first<A, B>(): A = this.a"));
+        assert_eq!("1", scope.exec("Pair(1, \"a\").first()"));
+        let infer = |str: &str| scope.read(str).infer_type(&scope).map(|t| t.print());
+        assert_eq!(Ok("Float".to_owned()), infer("p.norm()"));
+        assert_eq!(Ok("Float".to_owned()), infer("p.x"));
+        assert_eq!(Ok("Str".to_owned()), infer("Pair(1, \"a\").b"));
+        assert_eq!(Ok("Int".to_owned()), infer("Pair(1, \"a\").first()"));
+        assert_eq!(vec!("Point.norm"), scope.completions("p.no"));
+    }
+
     #[test]
     fn test_print() {
         let mut scope = Scope::init();
@@ -239,7 +541,7 @@ mod tests {
         scope.exec("fun inc(a: Int): Int = { a + 1 }");
         assert_eq!("3", scope.exec("inc(2)"));
 
-        scope.exec("fun zero(): Int = { val x = 0 ; x }");
+        scope.exec("fun zero(): Int = {\n  val x = 0\n\n  x\n}");
         assert_eq!("0", scope.exec("zero()"));
 
         scope.exec(r#"fun fact(n: Int): Int = { if (n <= 1) 1 else n*fact(n-1)}"#);
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{CompletionItem, CompletionItemKind, CompletionOptions, Diagnostic, DiagnosticSeverity, Hover,
+                HoverContents, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+                PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentPositionParams,
+                TextDocumentSyncCapability, TextDocumentSyncKind, Url};
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+
+use crate::expr::Expr::Fun;
+use crate::if_else;
+use crate::parser::{parse_declarations, parse_program, parse_warnings, SyntaxError};
+use crate::scope::Scope;
+use crate::utils::{language, Resources};
+
+type LspError = Box<dyn Error + Sync + Send>;
+
+// Language Server Protocol server on stdin/stdout
+pub fn lsp_server() {
+    if let Err(e) = run() {
+        eprintln!("LSP server error: {}", e)
+    }
+}
+
+fn run() -> Result<(), LspError> {
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions { trigger_characters: Some(vec!(".".to_owned())), ..Default::default() }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    })?)?;
+    let mut server = Server::new();
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break
+                }
+                connection.sender.send(Message::Response(server.on_request(req)))?;
+            }
+            Message::Notification(not) => {
+                if let Some(diagnostics) = server.on_notification(not)? {
+                    connection.sender.send(Message::Notification(diagnostics))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    // the writer thread ends when the sender of the connection is dropped
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server {
+    // builtin functions only, the documents are never evaluated
+    scope: Scope,
+    documents: HashMap<Url, String>,
+    resources: Resources,
+}
+
+impl Server {
+    fn new() -> Server {
+        let mut scope = Scope::init();
+        scope.set_sandbox(true);
+        Server { scope, documents: HashMap::new(), resources: Resources::init(&language()) }
+    }
+
+    fn on_request(&self, req: Request) -> Response {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            Completion::METHOD => self.position_params(req, Completion::METHOD)
+                .map(|(text, pos)| serde_json::to_value(complete(&self.scope, &text, pos.position))),
+            HoverRequest::METHOD => self.position_params(req, HoverRequest::METHOD)
+                .map(|(text, pos)| serde_json::to_value(hover(&self.scope, &text, pos.position))),
+            GotoDefinition::METHOD => self.position_params(req, GotoDefinition::METHOD)
+                .map(|(text, pos)| serde_json::to_value(definition(&text, pos.position)
+                    .map(|range| Location { uri: pos.text_document.uri, range }))),
+            _ => return Response::new_err(id, ErrorCode::MethodNotFound as i32, format!("unsupported request {}", req.method)),
+        };
+        match result {
+            Ok(Ok(value)) => Response::new_ok(id, value),
+            Ok(Err(e)) => Response::new_err(id, ErrorCode::InternalError as i32, e.to_string()),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    // completion, hover and definition requests all start with a TextDocumentPositionParams
+    fn position_params(&self, req: Request, method: &str) -> Result<(String, TextDocumentPositionParams), LspError> {
+        let (_, pos) = req.extract::<TextDocumentPositionParams>(method)?;
+        let text = self.documents.get(&pos.text_document.uri).cloned().unwrap_or_default();
+        Ok((text, pos))
+    }
+
+    // updates the documents and returns the diagnostics to publish
+    fn on_notification(&mut self, not: Notification) -> Result<Option<Notification>, LspError> {
+        let uri = match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = not.extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)?;
+                self.documents.insert(params.text_document.uri.clone(), params.text_document.text);
+                params.text_document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let mut params = not.extract::<lsp_types::DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)?;
+                if let Some(change) = params.content_changes.pop() {
+                    self.documents.insert(params.text_document.uri.clone(), change.text);
+                }
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = not.extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(None)
+            }
+            _ => return Ok(None),
+        };
+        let diagnostics = diagnostics(&self.documents[&uri], &self.resources);
+        Ok(Some(Notification::new(PublishDiagnostics::METHOD.to_owned(), PublishDiagnosticsParams { uri, diagnostics, version: None })))
+    }
+}
+
+// the syntax error, otherwise the warnings
+fn diagnostics(text: &str, resources: &Resources) -> Vec<Diagnostic> {
+    let diagnostic = |e: SyntaxError, severity: DiagnosticSeverity| {
+        let start = Position::new(e.line as u32 - 1, e.column as u32 - 1);
+        Diagnostic {
+            range: Range::new(start, Position::new(start.line, start.character + 1)),
+            severity: Some(severity),
+            source: Some("grolang".to_owned()),
+            message: e.format(resources),
+            ..Default::default()
+        }
+    };
+    match parse_program(text) {
+        Ok(_) => parse_warnings(text, &[]).into_iter().map(|w| diagnostic(w, DiagnosticSeverity::WARNING)).collect(),
+        Err(e) => vec!(diagnostic(e, DiagnosticSeverity::ERROR)),
+    }
+}
+
+// functions and declared symbols, or methods when the word is preceded by a '.'
+fn complete(scope: &Scope, text: &str, pos: Position) -> Vec<CompletionItem> {
+    let line: Vec<char> = text.lines().nth(pos.line as usize).unwrap_or("").chars().collect();
+    let end = (pos.character as usize).min(line.len());
+    let start = line[..end].iter().rposition(|c| !is_word_char(*c) && *c != '.' && *c != '"').map(|p| p + 1).unwrap_or(0);
+    let input: String = line[start..end].iter().collect();
+    let mut items: Vec<CompletionItem> = match input.rfind('.') {
+        Some(p) => {
+            let mut names = scope.completions(&input);
+            if !names.iter().all(|n| n.contains('.')) || names.is_empty() {
+                // the receiver type is unknown: propose the methods of all types
+                names = scope.find_funs("").into_iter().filter(|n| n.split_once('.').is_some_and(|(_, m)| m.starts_with(&input[p + 1..]))).collect();
+            }
+            names.iter().map(|n| fun_item(scope, n, n.split_once('.').map(|s| s.1).unwrap_or(n))).collect()
+        }
+        None if input.is_empty() => vec!(),
+        None => {
+            let mut items: Vec<CompletionItem> = scope.find_funs(&input).iter().filter(|n| !n.contains('.')).map(|n| fun_item(scope, n, n)).collect();
+            for (name, _, _) in declarations(text) {
+                if name.starts_with(&input) {
+                    items.push(CompletionItem { label: name, kind: Some(CompletionItemKind::VARIABLE), ..Default::default() })
+                }
+            }
+            items
+        }
+    };
+    items.dedup_by(|a, b| a.label == b.label);
+    items
+}
+
+fn fun_item(scope: &Scope, name: &str, label: &str) -> CompletionItem {
+    CompletionItem {
+        label: label.to_owned(),
+        kind: Some(if_else!(name.contains('.'), CompletionItemKind::METHOD, CompletionItemKind::FUNCTION)),
+        detail: signature(scope, name),
+        ..Default::default()
+    }
+}
+
+fn signature(scope: &Scope, key: &str) -> Option<String> {
+    match scope.get(key) {
+        Some(Fun(name, types, _)) => Some(format!("{}: {}", name, types.print())),
+        _ => None,
+    }
+}
+
+// signatures of the builtin functions and methods with that name
+fn hover(scope: &Scope, text: &str, pos: Position) -> Option<Hover> {
+    let word = word_at(text, pos)?;
+    let suffix = format!(".{}", word);
+    let signatures: Vec<String> = scope.find_funs("").iter()
+        .filter(|n| **n == word || n.ends_with(&suffix))
+        .flat_map(|n| scope.find_overloads(n)).filter_map(|(key, _)| signature(scope, &key)).collect();
+    if_else!(signatures.is_empty(), None, Some(Hover {
+        contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: format!("```\n{}\n```", signatures.join("\n")) }),
+        range: None,
+    }))
+}
+
+fn definition(text: &str, pos: Position) -> Option<Range> {
+    let word = word_at(text, pos)?;
+    declarations(text).into_iter().find(|d| d.0 == word).map(|(name, line, column)| {
+        let start = Position::new(line as u32 - 1, column as u32 - 1);
+        Range::new(start, Position::new(start.line, start.character + name.chars().count() as u32))
+    })
+}
+
+// declarations of the document, or of the lines before the first syntax error
+fn declarations(text: &str) -> Vec<(String, usize, usize)> {
+    parse_declarations(text).unwrap_or_else(|e| {
+        let valid: Vec<&str> = text.lines().take(e.line - 1).collect();
+        parse_declarations(&valid.join("\n")).unwrap_or_default()
+    })
+}
+
+fn word_at(text: &str, pos: Position) -> Option<String> {
+    let line: Vec<char> = text.lines().nth(pos.line as usize)?.chars().collect();
+    let at = (pos.character as usize).min(line.len());
+    let start = line[..at].iter().rposition(|c| !is_word_char(*c)).map(|p| p + 1).unwrap_or(0);
+    let end = line[at..].iter().position(|c| !is_word_char(*c)).map(|p| p + at).unwrap_or(line.len());
+    if_else!(start < end, Some(line[start..end].iter().collect()), None)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(items: Vec<CompletionItem>) -> Vec<String> { items.into_iter().map(|i| i.label).collect() }
+
+    #[test]
+    fn test_diagnostics() {
+        let resources = Resources::init("EN");
+        assert!(diagnostics("val a = 1\nprint(a)", &resources).is_empty());
+        let diagnostics = diagnostics("val a = 1\nprint(a", &resources);
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Position::new(1, 7), diagnostics[0].range.start);
+        assert_eq!("missing `)`", diagnostics[0].message);
+        let warnings = super::diagnostics("enum Color { Red, Green }\nval c = Red\nprint(match (c) { Red -> 1 })", &resources);
+        assert_eq!(Some(DiagnosticSeverity::WARNING), warnings[0].severity);
+        assert_eq!(Position::new(2, 6), warnings[0].range.start);
+        assert_eq!("the match does not handle the variants Green, add them or an `else` case", warnings[0].message);
+    }
+
+    #[test]
+    fn test_complete() {
+        let scope = Scope::init();
+        let text = "val total = 1\nfun triple(x: Int): Int = x * 3\nt\nprint(\"a\".tr";
+        assert_eq!(vec!("test", "toJson", "to_str", "total", "triple"), labels(complete(&scope, text, Position::new(2, 1))));
+        assert_eq!(vec!("trim"), labels(complete(&scope, text, Position::new(3, 12))));
+        assert_eq!(Some("Str.trim: (Str)->Str".to_owned()), complete(&scope, text, Position::new(3, 12))[0].detail);
+        assert_eq!(vec!("isFailure", "isSuccess"), labels(complete(&scope, "x.is", Position::new(0, 4))));
+    }
+
+    #[test]
+    fn test_hover() {
+        let scope = Scope::init();
+        let hover = hover(&scope, "val s = readFile(\"a.txt\")", Position::new(0, 10)).unwrap();
+        assert_eq!(HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: "```\nreadFile: (Str)->Str!\n```".to_owned() }), hover.contents);
+        assert!(super::hover(&scope, "val s = 1", Position::new(0, 4)).is_none());
+    }
+
+    #[test]
+    fn test_definition() {
+        let text = "struct Point(x: Float, y: Float)\nval p = Point(1.0, 2.0)\nfun norm(): Float = 1.0\nprint(p, norm())";
+        assert_eq!(Some(Range::new(Position::new(0, 7), Position::new(0, 12))), definition(text, Position::new(1, 10)));
+        assert_eq!(Some(Range::new(Position::new(1, 4), Position::new(1, 5))), definition(text, Position::new(3, 6)));
+        assert_eq!(Some(Range::new(Position::new(2, 4), Position::new(2, 8))), definition(text, Position::new(3, 11)));
+        assert_eq!(None, definition(text, Position::new(3, 1)));
+    }
+}
@@ -1,18 +1,39 @@
-use std::{env};
-use grolang::{eval_line, LANG, repl, VERSION};
+use std::{env, process};
+use grolang::{eval_line, format_files, LANG, Options, repl, run_tests, VERSION};
 
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // NO_COLOR (https://no-color.org) disables colors when set to a non empty value
+    let no_color = remove_flag(&mut args, "--no-color") || env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let options = Options { sandbox: remove_flag(&mut args, "--sandbox"), color: !no_color };
+    let check = remove_flag(&mut args, "--check");
+    let junit = remove_option(&mut args, "--junit");
     if args.len() <= 1 {
-        repl()
+        repl(&options)
     } else {
         match args[1].as_ref() {
             "-v" => println!("{} v{}", LANG, VERSION),
-            "-e" => args.get(2).map(|e| eval_line(e)).unwrap_or(()),
+            "-e" => args.get(2).map(|e| eval_line(e, &options)).unwrap_or(()),
+            "test" => if !run_tests(&args[2..], junit.as_deref(), &options) { process::exit(1) },
+            "fmt" => if !format_files(&args[2..], check) { process::exit(1) },
+            #[cfg(feature = "lsp")]
+            "lsp" => grolang::lsp_server(),
             _ => println!("Unknown command: {}", args[1]),
         };
     }
 }
 
+fn remove_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let len = args.len();
+    args.retain(|a| a != flag);
+    args.len() != len
+}
 
+// removes an option followed by its value
+fn remove_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let pos = args.iter().position(|a| a == option).filter(|p| p + 1 < args.len())?;
+    let value = args.remove(pos + 1);
+    args.remove(pos);
+    Some(value)
+}
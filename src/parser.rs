@@ -1,7 +1,7 @@
 use std::borrow::ToOwned;
-use std::string::ToString;
 
 use lazy_static::lazy_static;
+use pest::error::{Error, ErrorVariant, InputLocation, LineColLocation};
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest::pratt_parser::{Op, PrattParser};
@@ -11,10 +11,11 @@ use pest_derive::Parser;
 use crate::expr::{Expr, FALSE, NIL, TRUE};
 use crate::if_else;
 use crate::types::Type;
+use crate::utils::Resources;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
-struct GroParser;
+pub(crate) struct GroParser;
 
 lazy_static! {
     static ref PARSER: PrattParser<Rule> = {
@@ -29,53 +30,310 @@ lazy_static! {
     };
 }
 
+// the methods called by the operators, 'and' and 'or' are macros
+pub(crate) const OPERATOR_METHODS: [&str; 12] = ["add", "sub", "mul", "div", "mod", "exp", "eq", "neq", "le", "lt", "ge", "gt"];
+
+// the error is the code of the syntax error
 pub fn parse(str: &str) -> Result<Expr, String> {
-    match GroParser::parse(Rule::Statement, str) {
-        Ok(pairs) => Ok(parse_pairs(pairs)),
-        Err(e)    => Err(e.variant.to_string()),
+    match GroParser::parse(Rule::Line, str) {
+        Ok(mut pairs) => parse_primary(pairs.next().unwrap()).map_err(|e| SyntaxError::new(e, str).message),
+        Err(e)    => Err(SyntaxError::new(e, str).message),
+    }
+}
+
+// syntax error in a source text, line and column starting at 1. The message is a code like 'MissingClosing )'
+// which is localized with the resources
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(e: Error<Rule>, input: &str) -> SyntaxError {
+        let (line, column) = match e.line_col {
+            LineColLocation::Pos(p) => p,
+            LineColLocation::Span(p, _) => p,
+        };
+        let pos = match e.location {
+            InputLocation::Pos(p) => p,
+            InputLocation::Span((p, _)) => p,
+        };
+        let message = match &e.variant {
+            ErrorVariant::ParsingError { positives, .. } => syntax_code(input, pos, positives),
+            ErrorVariant::CustomError { message } => message.to_owned(),
+        };
+        SyntaxError { message, line, column }
+    }
+
+    pub fn format(&self, resources: &Resources) -> String { resources.get_syntax(&self.message) }
+}
+
+// parses a complete source text, made of statements separated by new lines or ';'
+pub fn parse_program(str: &str) -> Result<Vec<Expr>, SyntaxError> {
+    match GroParser::parse(Rule::Program, str) {
+        Ok(pairs) => pairs.filter(|p| p.as_rule() != Rule::EOI).map(parse_primary).collect::<Result<Vec<_>, _>>()
+            .map_err(|e| SyntaxError::new(e, str)),
+        Err(e)    => Err(SyntaxError::new(e, str)),
+    }
+}
+
+// parses a type like 'Map<Str, List<Int>>' or '(Int, Int) -> Bool'
+pub fn parse_type(str: &str) -> Result<Type, SyntaxError> {
+    match GroParser::parse(Rule::TypeLine, str) {
+        Ok(mut pairs) => to_type(pairs.next().unwrap()).map_err(|e| SyntaxError::new(e, str)),
+        Err(e)    => Err(SyntaxError::new(e, str)),
+    }
+}
+
+const OPERATORS: &str = "+-*/%^=<>&|!";
+
+// code of the error at a position, with targeted hints for the usual mistakes of beginners,
+// otherwise deduced from the brackets not closed or from the token at the position
+fn syntax_code(input: &str, pos: usize, positives: &[Rule]) -> String {
+    let rest = input[pos..].trim_start_matches([' ', '\t']);
+    let before = input[..pos].rsplit('\n').next().unwrap_or("").trim();
+    let words: Vec<&str> = before.split(|c: char| !c.is_alphanumeric() && c != '_').filter(|w| !w.is_empty()).collect();
+    let open = open_brackets(&input[..pos]);
+    let at_end = |text: &str| text.trim().is_empty() || text.starts_with('#');
+    // the end of the input, a wrong closing bracket or a block after a condition, possibly after a literal of a list
+    let missing = |text: &str| at_end(text) || text.starts_with(|c: char| ")]}".contains(c)) && open.last().map(|o| matching(*o)) != text.chars().next()
+        || text.starts_with('{') && open.last() == Some(&'(');
+    match words.first().copied() {
+        _ if rest.starts_with('"') && string_end(rest).is_none() => "StringNotClosed".to_owned(),
+        Some("let") if words.len() == 1 => "UseValOrVar let".to_owned(),
+        _ if rest.starts_with('=') && !rest.starts_with("==") && open.last() == Some(&'(') && (words.contains(&"if") || words.contains(&"while")) =>
+            "EqualsInCondition".to_owned(),
+        _ if positives.contains(&Rule::TypeExpr) => "ExpectedType".to_owned(),
+        Some("fun" | "struct") if positives.contains(&Rule::RawType) && open.last() == Some(&'(') =>
+            format!("MissingParameterType {}", words.last().unwrap_or(&"")),
+        Some("fun") if positives.contains(&Rule::RawType) && open.is_empty() => "MissingReturnType".to_owned(),
+        Some(keyword @ ("val" | "var" | "const")) if words.len() == 1 && positives.contains(&Rule::Symbol) => format!("ExpectedName {}", keyword),
+        Some("val" | "var" | "const") if positives.contains(&Rule::RawType) && !before.contains('=') => "ExpectedEquals".to_owned(),
+        _ if !open.is_empty() && (missing(rest) || missing(rest[token(rest).len()..].trim_start())) =>
+            format!("MissingClosing {}", matching(*open.last().unwrap())),
+        _ if before.ends_with('.') => "ExpectedMethod".to_owned(),
+        _ if positives.iter().any(|r| matches!(r, Rule::Expr | Rule::Symbol | Rule::Int)) && before.ends_with(|c: char| OPERATORS.contains(c)) =>
+            format!("ExpectedValue {}", &before[before.trim_end_matches(|c: char| OPERATORS.contains(c)).len()..]),
+        _ if at_end(rest) => "IncompleteStatement".to_owned(),
+        _ => format!("UnexpectedToken {}", token(rest)),
     }
 }
 
-fn parse_pairs(pairs: Pairs<Rule>) -> Expr {
+// the brackets opened before a position, ignoring strings and comments
+fn open_brackets(text: &str) -> Vec<char> {
+    let mut open = Vec::new();
+    let (mut in_string, mut escaped, mut comment) = (false, false, false);
+    for c in text.chars() {
+        match c {
+            '\n' => comment = false,
+            _ if comment => {}
+            '\\' if in_string => { escaped = !escaped; continue }
+            '"' if !escaped => in_string = !in_string,
+            _ if in_string => {}
+            '#' => comment = true,
+            '(' | '[' | '{' => open.push(c),
+            ')' | ']' | '}' => { open.pop(); }
+            _ => {}
+        }
+        escaped = false
+    }
+    open
+}
+
+fn matching(bracket: char) -> char {
+    match bracket {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+// length of the string literal at the start of a text
+fn string_end(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    text.char_indices().skip(1).find(|(_, c)| {
+        let end = !escaped && *c == '"';
+        escaped = !escaped && *c == '\\';
+        end
+    }).map(|(p, _)| p + 1)
+}
+
+// the string, the word or the character at the start of a text
+fn token(text: &str) -> &str {
+    let end = match text.chars().next() {
+        Some('"') => string_end(text).unwrap_or(text.len()),
+        Some(c) if c.is_alphanumeric() || c == '_' => text.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(text.len()),
+        Some(c) => c.len_utf8(),
+        None => 0,
+    };
+    &text[..end]
+}
+
+// symbols declared with val, var, const, fun, struct, enum or interface, with the line and column of their name
+#[cfg(feature = "lsp")]
+pub fn parse_declarations(str: &str) -> Result<Vec<(String, usize, usize)>, SyntaxError> {
+    let pairs = GroParser::parse(Rule::Program, str).map_err(|e| SyntaxError::new(e, str))?;
+    Ok(pairs.flatten()
+        .filter(|p| matches!(p.as_rule(), Rule::Declaration | Rule::Definition | Rule::Struct | Rule::Enum | Rule::Interface))
+        .filter_map(|p| p.into_inner().find(|i| i.as_rule() == Rule::Symbol))
+        .map(|s| {
+            let (line, column) = s.line_col();
+            (s.as_str().to_owned(), line, column)
+        }).collect())
+}
+
+// warnings about a program which can be parsed: the matches on an enum which do not handle all its variants
+pub fn parse_warnings(str: &str, known_enums: &[Vec<String>]) -> Vec<SyntaxError> {
+    let Ok(pairs) = GroParser::parse(Rule::Program, str) else { return vec!() };
+    let pairs: Vec<Pair<Rule>> = pairs.flatten().collect();
+    let mut enums: Vec<Vec<&str>> = pairs.iter().filter(|p| p.as_rule() == Rule::Enum)
+        .map(|p| p.clone().into_inner().skip(1).map(|v| v.into_inner().next().unwrap().as_str()).collect()).collect();
+    enums.extend(known_enums.iter().map(|e| e.iter().map(|v| v.as_str()).collect()));
+    let is_variant = |name: &str| enums.iter().any(|e| e.contains(&name));
+    pairs.iter().filter(|p| p.as_rule() == Rule::Match).filter_map(|m| {
+        let (mut named, mut handled, mut complete) = (Vec::new(), Vec::new(), false);
+        for case in m.clone().into_inner().filter(|c| c.as_rule() == Rule::Case) {
+            let guarded = case.clone().into_inner().any(|p| p.as_rule() == Rule::Guard);
+            let pattern = case.into_inner().next().unwrap();
+            let rule = pattern.as_rule();
+            let inner = pattern.into_inner();
+            let name = inner.clone().take_while(|p| p.as_rule() == Rule::Symbol).last().map(|s| s.as_str()).unwrap_or("");
+            // a variant is handled when the patterns of its fields accept any value
+            let any_fields = inner.skip_while(|p| p.as_rule() == Rule::Symbol).all(|p| p.as_rule() == Rule::Wildcard || p.as_rule() == Rule::NamePattern && !is_variant(p.as_str()));
+            match rule {
+                Rule::Else | Rule::Wildcard => complete |= !guarded,
+                Rule::NamePattern if !is_variant(name) => complete |= !guarded,
+                Rule::NamePattern | Rule::VariantPattern => {
+                    named.push(name);
+                    if !guarded && any_fields {
+                        handled.push(name)
+                    }
+                }
+                _ => {}
+            }
+        }
+        let variants = enums.iter().find(|e| named.iter().any(|n| e.contains(n)))?;
+        let missing: Vec<&str> = variants.iter().filter(|v| !handled.contains(v)).copied().collect();
+        if complete || missing.is_empty() {
+            return None
+        }
+        let (line, column) = m.line_col();
+        Some(SyntaxError { message: format!("NonExhaustiveMatch {}", missing.join(", ")), line, column })
+    }).collect()
+}
+
+fn parse_pairs(pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
     PARSER
         .map_primary(|p| parse_primary(p))
-        .map_infix(|left, op, right| reduce_expr(left, op, right))
+        .map_infix(|left, op, right| Ok(reduce_expr(left?, op, right?)))
         .parse(pairs)
 }
 
 fn reduce_expr(left: Expr, op: Pair<Rule>, right: Expr) -> Expr {
     if op.as_rule() == Rule::Dot {
-        if let Expr::Call(name, mut args) = right {
-            args.insert(0, left);
-            return Expr::Call(name, args)
+        match right {
+            Expr::Call(name, mut args) => {
+                args.insert(0, left);
+                return Expr::Call(name, args)
+            }
+            // a field, or a variant qualified by its enum, never a call
+            Expr::Symbol(name) => return Expr::Call(".".to_owned(), vec!(left, Expr::Symbol(name))),
+            _ => {}
         }
     }
     Expr::Call(to_operator_name(op), vec!(left, right))
 }
 
 
-fn parse_primary(pair: Pair<Rule>) -> Expr {
-    match pair.as_rule() {
+// the errors are the types which cannot be built, like 'List<Int, Int>'
+fn parse_primary(pair: Pair<Rule>) -> Result<Expr, Error<Rule>> {
+    Ok(match pair.as_rule() {
         Rule::Int => Expr::Int(pair.as_str().trim().replace("_", "").parse::<i64>().unwrap()),
         Rule::Float => Expr::Float(pair.as_str().parse::<f64>().unwrap()),
         Rule::Special => to_literal(pair.as_str()),
+        Rule::Null => NIL,
         Rule::String => Expr::Str(un_quote(pair.as_str())),
         Rule::Symbol | Rule::VarType => Expr::Symbol(pair.as_str().to_owned()),
-        Rule::RawType => Expr::TypeOf(Type::from_str(remove_first(pair.as_str()).trim()).unwrap()), // TODO: handle unwrap
+        Rule::RawType => Expr::TypeOf(to_type(pair)?),
         Rule::Operator => Expr::Symbol(pair.as_str().to_owned()),
-        Rule::Expr =>  parse_pairs(pair.into_inner()),
-        Rule::CallExpr => build_call(to_vec(pair, 0, 0)),
-        Rule::Declaration => build_call(to_vec(pair, 4, 2)),
-        Rule::Assignment => Expr::Call("assign".to_owned(), to_vec(pair, 0, 0)),
-        Rule::IfElse =>  Expr::Call("if".to_owned(), to_vec(pair, 3, 0 )),
-        Rule::While => Expr::Call("while".to_owned(), to_vec(pair, 0, 0)),
-        Rule::Block => Expr::Block(to_vec(pair, 0, 0)),
-        Rule::Definition => Expr::Call("fun".to_owned(), to_vec(pair, 0, 0)),
-        Rule::List  => build_list(to_vec(pair, 0, 0)),
-        Rule::Map  =>  build_map(to_vec(pair, 0, 0)),
-        Rule::Parameters  => build_params(pair.into_inner()),
-        Rule::Struct  =>  Expr::Call("struct".to_owned(), to_vec(pair, 0, 0)),
+        Rule::Expr =>  parse_pairs(pair.into_inner())?,
+        Rule::CallExpr => build_call(to_vec(pair, 0, 0)?),
+        Rule::Declaration => build_call(to_vec(pair, 4, 2)?),
+        Rule::Assignment => Expr::Call("assign".to_owned(), to_vec(pair, 0, 0)?),
+        Rule::IfElse =>  Expr::Call("if".to_owned(), to_vec(pair, 3, 0 )?),
+        Rule::While => Expr::Call("while".to_owned(), to_vec(pair, 0, 0)?),
+        Rule::Block => Expr::Block(to_vec(pair, 0, 0)?),
+        Rule::Definition => build_declaration("fun", pair)?,
+        Rule::List  => build_list(to_vec(pair, 0, 0)?),
+        Rule::Map  =>  build_map(to_vec(pair, 0, 0)?),
+        Rule::Parameters  => build_params(pair.into_inner())?,
+        Rule::Struct  =>  build_declaration("struct", pair)?,
+        Rule::Enum  =>  build_declaration("enum", pair)?,
+        Rule::Interface  =>  build_declaration("interface", pair)?,
+        Rule::Signature  =>  build_call(to_vec(pair, 0, 0)?),
+        Rule::Match  =>  Expr::Call("match".to_owned(), to_vec(pair, 0, 0)?),
+        Rule::Case  =>  Expr::Call("case".to_owned(), to_vec(pair, 3, 1)?),
+        Rule::Guard  =>  parse_primary(pair.into_inner().next().unwrap())?,
+        Rule::Else | Rule::Wildcard  =>  Expr::Symbol("_".to_owned()),
+        Rule::RangePattern  =>  Expr::Call("range".to_owned(), to_vec(pair, 0, 0)?),
+        Rule::TypePattern  =>  Expr::TypeOf(to_type(pair.into_inner().last().unwrap())?),
+        Rule::ListPattern  =>  build_list(to_vec(pair, 0, 0)?),
+        Rule::MapPattern  =>  build_map(to_vec(pair, 0, 0)?),
+        // the enum of a qualified variant is not needed to match it
+        Rule::VariantPattern | Rule::NamePattern  =>  {
+            let rule = pair.as_rule();
+            let names = pair.clone().into_inner().take_while(|p| p.as_rule() == Rule::Symbol).count();
+            let mut args = to_vec(pair, 0, 0)?;
+            args.drain(..names - 1);
+            if_else!(rule == Rule::NamePattern, args.remove(0), build_call(args))
+        }
+        Rule::Variant  =>  build_call(to_vec(pair, 0, 0)?),
+        Rule::Test  =>  Expr::Call("test".to_owned(), to_vec(pair, 0, 0)?),
         _ => panic!("Rule '{}' not implemented", to_operator_name(pair))
+    })
+}
+
+// a name which is not a builtin type is a structure, the type parameters of a generic declaration included
+fn to_type(pair: Pair<Rule>) -> Result<Type, Error<Rule>> {
+    let span = pair.as_span();
+    let error = |code: String| Error::new_from_span(ErrorVariant::CustomError { message: code }, span);
+    let rule = pair.as_rule();
+    let mut inner = pair.into_inner();
+    match rule {
+        Rule::RawType => to_type(inner.next().unwrap()),
+        Rule::TypeExpr => {
+            let t = to_type(inner.next().unwrap())?;
+            Ok(inner.fold(t, |t, s| if_else!(s.as_str() == "?", Type::Option(Box::new(t)), Type::Try(Box::new(t)))))
+        }
+        Rule::FunType => {
+            let mut args = inner.map(to_type).collect::<Result<Vec<_>, _>>()?;
+            let output = args.pop().unwrap();
+            Ok(Type::Fun(args, Box::new(output)))
+        }
+        Rule::TupleType => {
+            let mut args = inner.map(to_type).collect::<Result<Vec<_>, _>>()?;
+            Ok(if_else!(args.len() == 1, args.remove(0), Type::Tuple(args)))
+        }
+        _ => {
+            let name = inner.next().unwrap().as_str();
+            let mut args = inner.map(to_type).collect::<Result<Vec<_>, _>>()?;
+            match (name, args.len()) {
+                ("Any", 0) => Ok(Type::Any),
+                ("Int", 0) => Ok(Type::Int),
+                ("Bool", 0) => Ok(Type::Bool),
+                ("Str", 0) => Ok(Type::Str),
+                ("Float", 0) => Ok(Type::Float),
+                ("Number", 0) => Ok(Type::Number),
+                ("Macro", 0) => Ok(Type::Macro),
+                ("List", 1) => Ok(Type::List(Box::new(args.remove(0)))),
+                ("Map", 2) => Ok(Type::Map(Box::new(args.remove(0)), Box::new(args.remove(0)))),
+                ("Any" | "Int" | "Bool" | "Str" | "Float" | "Number" | "Macro" | "List" | "Map", _) => Err(error(format!("TypeArguments {}", name))),
+                _ if name.starts_with(|c: char| c.is_ascii_uppercase()) => Ok(Type::Struct(name.to_owned(), args)),
+                _ => Err(error(format!("UnknownType {}", name))),
+            }
+        }
     }
 }
 
@@ -85,26 +343,93 @@ fn build_call(mut args: Vec<Expr>) -> Expr {
     } else { panic!("first arg should be a symbol") }
 }
 
+// the doc comment is added as last argument. The type of a structure is added after its fields, followed by
+// its interfaces and the methods of its body
+fn build_declaration(name: &str, pair: Pair<Rule>) -> Result<Expr, Error<Rule>> {
+    let doc = doc_comment(&pair);
+    let params: Vec<String> = pair.clone().into_inner().find(|p| p.as_rule() == Rule::TypeParams)
+        .map(|p| p.into_inner().map(|s| s.as_str().to_owned()).collect()).unwrap_or_default();
+    let receiver = pair.clone().into_inner().find(|p| p.as_rule() == Rule::Receiver)
+        .map(|p| to_type(p.into_inner().next().unwrap())).transpose()?.map(|t| t.with_params(&params));
+    let members: Vec<Pair<Rule>> = pair.clone().into_inner().filter(|p| matches!(p.as_rule(), Rule::Interfaces | Rule::Methods)).collect();
+    let mut args: Vec<Expr> = pair.into_inner().filter(|p| !matches!(p.as_rule(), Rule::TypeParams | Rule::Receiver | Rule::Interfaces | Rule::Methods))
+        .map(|p| parse_primary(p).map(|e| with_type_params(e, &params))).collect::<Result<_, _>>()?;
+    if let Some(receiver) = receiver {
+        add_receiver(&mut args, receiver)
+    }
+    if name == "struct" {
+        let symbol = args[0].to_symbol().unwrap_or_default().to_owned();
+        let struct_type = Type::Struct(symbol, params.iter().map(|p| Type::Param(p.to_owned())).collect());
+        args.push(Expr::TypeOf(struct_type.clone()));
+        for member in members.into_iter().flat_map(|m| m.into_inner()) {
+            match (member.as_rule(), with_type_params(parse_primary(member)?, &params)) {
+                (Rule::Symbol, Expr::Symbol(interface)) => args.push(Expr::TypeOf(Type::Struct(interface, vec!()))),
+                (_, Expr::Call(fun, mut method)) => {
+                    add_receiver(&mut method, struct_type.clone());
+                    args.push(Expr::Call(fun, method))
+                }
+                _ => {}
+            }
+        }
+    }
+    args.extend(doc.map(Expr::Str));
+    Ok(Expr::Call(name.to_owned(), args))
+}
+
+// a method is registered with the name of the type of its receiver, which is its first parameter 'this'
+fn add_receiver(args: &mut [Expr], receiver: Type) {
+    if let [Expr::Symbol(name), Expr::Params(params), ..] = args {
+        *name = receiver.method_name(name);
+        params.insert(0, ("this".to_owned(), receiver));
+    }
+}
+
+// the types of a generic declaration, including the ones declared in its body, use its type parameters
+fn with_type_params(expr: Expr, params: &[String]) -> Expr {
+    if params.is_empty() {
+        return expr
+    }
+    match expr {
+        Expr::TypeOf(t) => Expr::TypeOf(t.with_params(params)),
+        Expr::Params(vec) => Expr::Params(vec.into_iter().map(|(n, t)| (n, t.with_params(params))).collect()),
+        Expr::Call(name, args) => Expr::Call(name, args.into_iter().map(|e| with_type_params(e, params)).collect()),
+        Expr::Block(vec) => Expr::Block(vec.into_iter().map(|e| with_type_params(e, params)).collect()),
+        _ => expr,
+    }
+}
+
+// lines starting with '##' just above a declaration
+fn doc_comment(pair: &Pair<Rule>) -> Option<String> {
+    let mut lines: Vec<&str> = pair.get_input()[..pair.as_span().start()].split('\n').collect();
+    if !lines.pop()?.trim().is_empty() {
+        return None
+    }
+    let mut doc: Vec<&str> = lines.iter().rev().map(|l| l.trim()).take_while(|l| l.starts_with("##")).map(|l| l[2..].trim()).collect();
+    doc.reverse();
+    if_else!(doc.is_empty(), None, Some(doc.join("\n")))
+}
+
 fn build_list(args: Vec<Expr>) -> Expr {
     Expr::List(Type::infer_list(&args), args)
 }
 
 fn build_map(args: Vec<Expr>) -> Expr {
-    let pairs: Vec<(Expr, Expr)> = args.chunks(2).into_iter().flat_map(|p| if_else!(p.len() == 2, Some((p[0].clone(), p[1].clone())), None)).collect();
+    let pairs: Vec<(Expr, Expr)> = args.chunks(2).flat_map(|p| if_else!(p.len() == 2, Some((p[0].clone(), p[1].clone())), None)).collect();
     Expr::Map(Type::infer_map(&pairs), pairs)
 }
 
 
 
-fn build_params(pairs: Pairs<Rule>) -> Expr {
-    Expr::Params(pairs.into_iter().map(|p| {
-        let s: Vec<&str> = p.as_str().split(":").collect();
-        (s[0].trim().to_string(), Type::from_str(s[1].trim()).unwrap())
-    }).collect::<Vec<_>>())
+fn build_params(pairs: Pairs<Rule>) -> Result<Expr, Error<Rule>> {
+    pairs.into_iter().map(|p| {
+        let mut inner = p.into_inner();
+        let name = inner.next().unwrap().as_str().to_owned();
+        to_type(inner.next().unwrap()).map(|t| (name, t))
+    }).collect::<Result<Vec<_>, _>>().map(Expr::Params)
 }
 
-fn to_vec(pair: Pair<Rule>, expected_len: usize, optional_pos: usize) -> Vec<Expr> {
-    let mut args: Vec<Expr> = pair.into_inner().into_iter().map(|p| parse_primary(p)).collect();
+fn to_vec(pair: Pair<Rule>, expected_len: usize, optional_pos: usize) -> Result<Vec<Expr>, Error<Rule>> {
+    let mut args: Vec<Expr> = pair.into_inner().map(|p| parse_primary(p)).collect::<Result<_, _>>()?;
     if expected_len > 0 && args.len() < expected_len {
         if optional_pos > 0 {
             args.insert(optional_pos, Expr::Nil)
@@ -112,16 +437,12 @@ fn to_vec(pair: Pair<Rule>, expected_len: usize, optional_pos: usize) -> Vec<Exp
             args.resize(expected_len, Expr::Nil)
         }
     }
-    args
+    Ok(args)
 }
 
 
 fn un_quote(str: &str) -> String {
-    (&str[1..str.len()-1]).to_owned()
-}
-
-fn remove_first(str: &str) -> String {
-    (&str[1..str.len()]).to_owned()
+    str[1..str.len()-1].to_owned()
 }
 
 fn to_operator_name(pair: Pair<Rule>) -> String {
@@ -159,6 +480,7 @@ mod tests {
         assert_eq!(Expr::Str("escaped \\n \\t \\\" \\\\ string".to_owned()), parse(r#""escaped \n \t \" \\ string""#).unwrap());
         assert_eq!(Expr::TypeOf(Type::Float), parse(": Float").unwrap());
         assert_eq!(Expr::TypeOf(Type::List(Box::new(Type::Int))), parse(":List<Int>").unwrap());
+        assert_eq!(Expr::TypeOf(Type::Option(Box::new(Type::Fun(vec!(Type::Int), Box::new(Type::Bool))))), parse(": ((Int) -> Bool)?").unwrap());
     }
 
     #[test]
@@ -177,12 +499,119 @@ mod tests {
 
     #[test]
     fn test_struct() {
-        assert_eq!("Call(struct, [Symbol(Point), Params([(x, Float), (y, Float)])])", read("struct Point(x: Float, y:Float)"));
+        assert_eq!("Call(struct, [Symbol(Point), Params([(x, Float), (y, Float)]), TypeOf(Struct(Point, []))])", read("struct Point(x: Float, y:Float)"));
+    }
+
+    #[test]
+    fn test_enum() {
+        assert_eq!("Call(enum, [Symbol(Shape), Call(Dot, []), Call(Circle, [Params([(r, Float)])])])", read("enum Shape {\n  Dot,\n  Circle(r: Float)\n}"));
+        assert_eq!("Call(., [Symbol(Color), Symbol(Red)])", read("Color.Red"));
+        assert_eq!("Map(Map(Any, Any), [(Symbol(Red), Str(red)), (Str(a), Nil)])", read("{Red: \"red\", \"a\": null}"));
+    }
+
+    #[test]
+    fn test_interface() {
+        assert_eq!("Call(interface, [Symbol(Shape), Call(area, [Params([]), TypeOf(Float)]), Call(scale, [Params([(k, Float)]), TypeOf(Struct(Shape, []))])])",
+                   read("interface Shape {\n  fun area(): Float\n  fun scale(k: Float): Shape\n}"));
+        assert_eq!("Call(struct, [Symbol(Circle), Params([(r, Float)]), TypeOf(Struct(Circle, [])), TypeOf(Struct(Shape, [])), \
+                    Call(fun, [Symbol(Circle.area), Params([(this, Struct(Circle, [])), (k, Int)]), TypeOf(Float), Call(., [Symbol(this), Symbol(r)])])])",
+                   read("struct Circle(r: Float) : Shape { fun area(k: Int): Float = this.r }"));
+    }
+
+    #[test]
+    fn test_match() {
+        assert_eq!("Call(match, [Symbol(x), Call(case, [Call(range, [Int(1), Int(9)]), Nil, Str(a)]), Call(case, [TypeOf(Int), Call(gt, [Symbol(x), Int(0)]), Str(b)]), \
+                    Call(case, [Call(Circle, [Symbol(r), Symbol(_)]), Nil, Symbol(r)]), Call(case, [Symbol(_), Nil, Nil])])",
+                   read("match (x) { 1..9 -> \"a\"; is Int if x > 0 -> \"b\"; Shape.Circle(r, _) -> r; else -> nil }"));
+        let warnings = |str: &str| parse_warnings(str, &[]).iter().map(|w| format!("{}:{} {}", w.line, w.column, w.message)).collect::<Vec<_>>();
+        let colors = "enum Color { Red, Green, Blue(x: Int) }\n";
+        assert_eq!(vec!("2:1 NonExhaustiveMatch Green, Blue"), warnings(&format!("{}match (c) {{ Red -> 1; Blue(1) -> 2 }}", colors)));
+        assert_eq!(vec!("2:1 NonExhaustiveMatch Red"), warnings(&format!("{}match (c) {{ Red if true -> 1; Green -> 2; Color.Blue(_) -> 3 }}", colors)));
+        assert!(warnings(&format!("{}match (c) {{ Red -> 1; Green -> 2; Blue(x) -> 3 }}", colors)).is_empty());
+        assert!(warnings(&format!("{}match (c) {{ Red -> 1; other -> 2 }}", colors)).is_empty());
+        assert!(warnings("match (c) { 1 -> 1 }").is_empty());
+        // the enums can be declared before the source, like in the REPL
+        let known = vec!(vec!("On".to_owned(), "Off".to_owned()));
+        assert_eq!(vec!("NonExhaustiveMatch Off"), parse_warnings("match (s) { On -> 1 }", &known).iter().map(|w| w.message.clone()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_generics() {
+        assert_eq!("Call(struct, [Symbol(Pair), Params([(a, Param(A)), (b, List(Param(B)))]), TypeOf(Struct(Pair, [Param(A), Param(B)]))])",
+                   read("struct Pair<A, B>(a: A, b: List<B>)"));
+        assert_eq!("Call(fun, [Symbol(first), Params([(l, List(Param(T)))]), TypeOf(Option(Param(T))), Block([Call(val, [Symbol(x), TypeOf(Param(T)), Symbol(y)])])])",
+                   read("fun first<T>(l: List<T>): T? = { val x: T = y }"));
+        assert_eq!("Call(val, [Symbol(p), TypeOf(Struct(Pair, [Int, Str])), Symbol(q)])", read("val p: Pair<Int, Str> = q"));
+    }
+
+    #[test]
+    fn test_methods() {
+        assert_eq!("Call(fun, [Symbol(Str.shout), Params([(this, Str), (n, Int)]), TypeOf(Str), Symbol(this)])", read("fun Str.shout(n: Int): Str = this"));
+        assert_eq!("Call(fun, [Symbol(List.first), Params([(this, List(Param(T)))]), TypeOf(Param(T)), Symbol(x)])", read("fun List<T>.first<T>(): T = x"));
+        assert_eq!("Call(add, [Call(., [Symbol(p), Symbol(x)]), Call(., [Call(., [Symbol(p), Symbol(b)]), Symbol(y)])])", read("p.x + p.b.y"));
+        assert_eq!(Err("UnknownType foo".to_owned()), parse("fun foo.bar(): Int = 1"));
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let program = parse_program("## Increments\n## a number\nfun inc(x: Int): Int = x + 1\n# not a doc\nstruct Point(x: Float)\n##\nval a = 1").unwrap();
+        assert_eq!("Call(fun, [Symbol(inc), Params([(x, Int)]), TypeOf(Int), Call(add, [Symbol(x), Int(1)]), Str(Increments\\na number)])", format!("{:?}", program[0]).replace("\"", ""));
+        assert_eq!("Call(struct, [Symbol(Point), Params([(x, Float)]), TypeOf(Struct(Point, []))])", format!("{:?}", program[1]).replace("\"", ""));
+    }
+
+    #[test]
+    fn test_test() {
+        assert_eq!("Call(test, [Str(addition), Block([Call(assertEquals, [Int(2), Call(add, [Int(1), Int(1)])])])])", read("test \"addition\" { assertEquals(2, 1 + 1) }"));
+    }
+
+    #[test]
+    fn test_program() {
+        let program = parse_program("# a comment\nval a = 1 # another one\n\n\tfun inc(x: Int): Int = {\n  x + 1\n}\ninc(a) ; a\n").unwrap();
+        assert_eq!("[Call(val, [Symbol(a), Nil, Int(1)]), Call(fun, [Symbol(inc), Params([(x, Int)]), TypeOf(Int), Block([Call(add, [Symbol(x), Int(1)])])]), Call(inc, [Symbol(a)]), Symbol(a)]",
+                   format!("{:?}", program).replace("\"", ""));
+        assert_eq!(Ok(vec!()), parse_program("\n# nothing\n"));
+        let error = parse_program("val a = 1 val b = 2").unwrap_err();
+        assert_eq!((1, 11), (error.line, error.column));
+    }
+
+    #[test]
+    #[cfg(feature = "lsp")]
+    fn test_declarations_positions() {
+        let source = "val a = 1\nstruct Point(x: Float, y: Float)\nfun inc(x: Int): Int = {\n  var b = x\n  b + 1\n}";
+        assert_eq!(Ok(vec!(("a".to_owned(), 1, 5), ("Point".to_owned(), 2, 8), ("inc".to_owned(), 3, 5), ("b".to_owned(), 4, 7))), parse_declarations(source));
     }
 
     #[test]
     fn test_errors() {
-        assert!(parse("=2").err().is_some())
+        let error = |s: &str| parse_program(s).unwrap_err().message;
+        assert!(parse("=2").err().is_some());
+        assert_eq!("MissingClosing )", error("print(a"));
+        assert_eq!("MissingClosing }", error("fun f(x: Int): Int = {\n  x + 1\n"));
+        assert_eq!("MissingClosing ]", error("val a = [1, 2)"));
+        assert_eq!("MissingClosing )", error("while (a < 1 { }"));
+        assert_eq!("UnexpectedToken )", error("print(a))"));
+        assert_eq!("StringNotClosed", error("val s = \"abc"));
+        assert_eq!("ExpectedEquals", error("val a 1"));
+        assert_eq!("ExpectedName val", error("val = 3"));
+        assert_eq!("ExpectedName var", error("var: Int = 3"));
+        assert_eq!("ExpectedValue =", error("val a ="));
+        assert_eq!("ExpectedValue +", error("1 +* 2"));
+        assert_eq!("ExpectedValue +", error("val a = 1 +"));
+        assert_eq!("IncompleteStatement", error("struct P(x: Int) :"));
+        assert_eq!("ExpectedType", error("val a: = 1"));
+        assert_eq!("ExpectedType", error("fun f(g: (Int) -> ): Int = 1"));
+        assert_eq!("TypeArguments Map", error("val m: Map<Str> = x"));
+        assert_eq!("UnknownType int", error("fun f(x: List<int>): Int = 1"));
+        assert_eq!("MissingParameterType x", error("fun f(x) = x"));
+        assert_eq!("MissingReturnType", error("fun f(x: Int) = x"));
+        assert_eq!("EqualsInCondition", error("if (a = 1) 2"));
+        assert_eq!("UseValOrVar let", error("let x = 1"));
+        assert_eq!("ExpectedMethod", error("x."));
+        assert_eq!("UnexpectedToken \"b\"", error("print(\"a\" \"b\")"));
+        let resources = Resources::init("EN");
+        assert_eq!("the parameter `x` has no type, write for example `x: Int`", parse_program("fun f(x) = x").unwrap_err().format(&resources));
+        assert_eq!("incomplete statement", parse_program("struct P(x: Int) :").unwrap_err().format(&resources));
+        assert_eq!("Poi!nt", resources.get_syntax("Poi!nt"));
     }
 
     #[test]
@@ -192,12 +621,14 @@ mod tests {
         assert_eq!("Call(var, [Symbol(a), Nil, Int(1)])", read("var a = 1"));
         assert_eq!("Call(var, [Symbol(l), Nil, List(List(Int), [Int(1), Int(2), Int(3)])])", read("var l = [1,2,3]"));
         assert_eq!("Call(var, [Symbol(l), TypeOf(List(Int)), List(List(Int), [Int(1), Int(2), Int(3)])])", read("var l :List<Int> = [1,2,3]"));
+        assert_eq!("Call(val, [Symbol(m), TypeOf(Map(Str, List(Tuple([Int, Float])))), Symbol(x)])", read("val m: Map<Str, List<(Int, Float)>> = x"));
     }
 
     #[test]
     fn test_assignments() {
         assert_eq!("Call(assign, [Symbol(a), Int(2)])", read("a = 2"));
         assert_eq!("Call(assign, [Symbol(a), Int(2)])", read("assign(a, 2)"));
+        assert_eq!("Call(assign, [Symbol(value), Int(2)])", read("value = 2"));
     }
 
     #[test]
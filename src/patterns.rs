@@ -0,0 +1,85 @@
+use crate::exception::Exception;
+use crate::expr::Expr;
+use crate::expr::Expr::{Call, Instance, List, Map, Nil, Symbol, TypeOf, Variant};
+use crate::functions::equals;
+use crate::scope::Scope;
+use crate::types::Type;
+
+// The cases of a match are Call(case, [pattern, guard, body]), the guard is nil when there is none
+
+// a match is an expression, evaluated like a function call with the scope of the caller
+pub fn run_match(args: &[Expr], scope: &Scope) -> Result<Expr, Exception> {
+    for case in &args[1..] {
+        if let Call(_, case) = case {
+            check_bindings(&case[0], scope, &mut Vec::new())?
+        }
+    }
+    let value = args[0].eval(scope)?;
+    for case in &args[1..] {
+        let Call(_, case) = case else { continue };
+        let mut bindings = Vec::new();
+        if !matches(&case[0], &value, scope, &mut bindings)? {
+            continue
+        }
+        // the variables bound by the pattern are only visible in the guard and the body of the case
+        let mut local = scope.child();
+        bindings.into_iter().for_each(|(name, value)| local.set(&name, value, Some(false)));
+        if case[1] == Nil || case[1].eval_mutable(&mut local)?.to_bool()? {
+            return case[2].eval_mutable(&mut local)
+        }
+    }
+    Err(Exception::NoMatchingCase(value.print()))
+}
+
+// a name is bound only once by a pattern, the variants are not bound
+fn check_bindings(pattern: &Expr, scope: &Scope, names: &mut Vec<String>) -> Result<(), Exception> {
+    match pattern {
+        Symbol(name) if name == "_" || is_variant(name, scope) => Ok(()),
+        Symbol(name) if names.contains(name) => Err(Exception::DuplicateBinding(name.to_owned())),
+        Symbol(name) => { names.push(name.to_owned()); Ok(()) }
+        Call(name, _) if name == "range" => Ok(()),
+        Call(_, patterns) | List(_, patterns) => patterns.iter().try_for_each(|p| check_bindings(p, scope, names)),
+        Map(_, patterns) => patterns.iter().try_for_each(|p| check_bindings(&p.1, scope, names)),
+        _ => Ok(()),
+    }
+}
+
+// a name of a variant is not bound, even without its enum when several enums declare it
+fn is_variant(name: &str, scope: &Scope) -> bool {
+    match scope.find(name) {
+        Some(value) => matches!(value, Variant(..)),
+        None => !scope.variant_enums(name).is_empty(),
+    }
+}
+
+// checks that a value matches a pattern, and adds the variables bound by the pattern
+fn matches(pattern: &Expr, value: &Expr, scope: &Scope, bindings: &mut Vec<(String, Expr)>) -> Result<bool, Exception> {
+    Ok(match (pattern, value) {
+        (Symbol(name), _) if name == "_" => true,
+        (Symbol(name), _) => match scope.find(name) {
+            Some(variant @ Variant(..)) => variant == *value,
+            // a variant declared by several enums is the one of the enum of the value
+            None if is_variant(name, scope) => matches!(value, Variant(Type::Enum(e), v, fields) if v == name && fields.is_empty() && scope.variant_enums(name).contains(e)),
+            _ => { bindings.push((name.to_owned(), value.clone())); true }
+        },
+        (TypeOf(t), _) => value.value_type().is_subtype_of(t, scope),
+        (Call(name, bounds), Expr::Int(_) | Expr::Float(_)) if name == "range" => {
+            let x = f64::try_from(value.clone())?;
+            f64::try_from(bounds[0].clone())? <= x && x <= f64::try_from(bounds[1].clone())?
+        }
+        (List(_, patterns), List(_, values)) => patterns.len() == values.len() && all_match(patterns.iter().zip(values), scope, bindings)?,
+        // the map can have other keys than the ones of the pattern
+        (Map(_, patterns), Map(_, pairs)) => {
+            let found: Option<Vec<(&Expr, &Expr)>> = patterns.iter().map(|(k, p)| pairs.iter().find(|v| equals(&v.0, k)).map(|v| (p, &v.1))).collect();
+            found.map(|f| all_match(f.into_iter(), scope, bindings)).transpose()?.unwrap_or(false)
+        }
+        (Call(name, patterns), Instance(Type::Struct(struct_name, _), fields)) | (Call(name, patterns), Variant(_, struct_name, fields)) =>
+            name == struct_name && patterns.len() == fields.len() && all_match(patterns.iter().zip(fields.iter().map(|f| &f.1)), scope, bindings)?,
+        (Call(..) | List(..) | Map(..), _) => false,
+        _ => equals(pattern, value),
+    })
+}
+
+fn all_match<'a>(mut pairs: impl Iterator<Item = (&'a Expr, &'a Expr)>, scope: &Scope, bindings: &mut Vec<(String, Expr)>) -> Result<bool, Exception> {
+    pairs.try_fold(true, |ok, (pattern, value)| Ok(ok && matches(pattern, value, scope, bindings)?))
+}
@@ -0,0 +1,259 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use rustyline::{Context, Editor, Helper};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{CmdKind, Highlighter};
+use rustyline::hint::{Hint, Hinter};
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+
+use crate::{if_else, interrupt, LANG, Options, VERSION};
+use crate::exception::Exception;
+use crate::expr::Expr;
+use crate::functions::{describe, type_declaration};
+use crate::highlight::{highlight, hint, HINT, STD};
+use crate::parser::parse_warnings;
+use crate::scope::Scope;
+use crate::types::Type;
+use crate::utils::{language, Resources};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+
+// in the order of the help, the first command starting with an abbreviation is selected
+const COMMANDS: [&str; 11] = ["quit", "history", "load", "save", "debug", "doc", "type", "env", "reset", "forget", "?"];
+
+// the line editor owns the scope of the session, for the completion and the signature hints
+struct GroHelper {
+    scope: Scope,
+    color: bool,
+    // false when the line is submitted
+    brackets: Cell<bool>,
+}
+
+// a signature is displayed after the cursor but never inserted in the line
+struct Signature(String);
+
+impl Hint for Signature {
+    fn display(&self) -> &str { &self.0 }
+    fn completion(&self) -> Option<&str> { None }
+}
+
+impl Hinter for GroHelper {
+    type Hint = Signature;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<Signature> {
+        if_else!(pos == line.len(), hint(&self.scope, line).map(Signature), None)
+    }
+}
+
+impl Highlighter for GroHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if_else!(self.color, Cow::Owned(highlight(line, if_else!(self.brackets.get(), Some(pos), None))), Cow::Borrowed(line))
+    }
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        if_else!(self.color, Cow::Owned(format!("{HINT}{hint}{STD}")), Cow::Borrowed(hint))
+    }
+    // the line is highlighted again when the cursor moves, for the matching brackets
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        self.brackets.set(kind != CmdKind::ForcedRefresh);
+        self.color
+    }
+}
+
+impl Completer for GroHelper {
+    type Candidate = String;
+
+    // functions starting with the word before the cursor, or methods when the word is preceded by an expression and a '.'
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(|c: char| !c.is_alphanumeric() && c != '_' && c != '.' && c != '"').map(|p| p + 1).unwrap_or(0);
+        let input = &line[start..pos];
+        let names = self.scope.completions(input);
+        Ok(match input.rfind('.') {
+            Some(p) => (start + p + 1, names.iter().filter_map(|n| n.split_once('.')).map(|(_, m)| m.to_owned()).collect()),
+            None => (start, names),
+        })
+    }
+}
+
+impl Validator for GroHelper {}
+
+impl Helper for GroHelper {}
+
+pub fn repl(options: &Options) {
+    let (red, blue, std) = if_else!(options.color, (RED, BLUE, STD), ("", "", ""));
+    let mut debug = false;
+    let resources = Resources::init(&language());
+    println!("{blue}{LANG} Version {VERSION}{std}\n{}\n", resources.help.split("\n").next().unwrap());
+    let Ok(mut editor) = Editor::<GroHelper, DefaultHistory>::new() else {
+        eprintln!("cannot initialize the terminal");
+        return
+    };
+    editor.set_helper(Some(GroHelper { scope: options.new_scope(), color: options.color, brackets: Cell::new(true) }));
+    interrupt::install();
+    // Ctrl-C or Ctrl-D on the prompt quits
+    'prompt: while let Ok(mut input) = editor.readline("gro> ") {
+        // an empty line ends an incomplete statement, which is then reported as a syntax error, Ctrl-C discards it
+        while let Some((end, separator)) = continuation(&input) {
+            match editor.readline("...  ") {
+                Ok(line) if !line.trim().is_empty() => input = input[..end].to_owned() + separator + &line,
+                Err(ReadlineError::Interrupted) => continue 'prompt,
+                _ => break,
+            }
+        }
+        let input = input.trim();
+        if input.is_empty() || input.starts_with('#') {
+            continue
+        }
+        let scope = &mut editor.helper_mut().unwrap().scope;
+        if let Some(line) = input.strip_prefix(':') {
+            let (command, arg) = line.split_once(' ').map(|(c, a)| (c, a.trim())).unwrap_or((line, ""));
+            match find_command(command) {
+                Some("quit") => break,
+                Some("debug") => { debug = !debug; println!("# debug={}", debug) },
+                Some("history") => editor.history().iter().rev().for_each(|e| println!("# {}", e)),
+                Some("load") if !arg.is_empty() => load(arg),
+                Some("save") if !arg.is_empty() => save(editor.history(), arg),
+                Some("doc") if !arg.is_empty() => match describe(arg, scope, &resources) {
+                    Ok(doc) => println!("{}", doc),
+                    Err(ex) => println!("{red}{} {std}", ex.format(&resources)),
+                },
+                Some("type") if !arg.is_empty() => match scope.read(arg).infer_type(scope) {
+                    Ok(t) => println!("{}", t.print()),
+                    Err(ex) => println!("{red}{} {std}", ex.format(&resources)),
+                },
+                Some("env") => scope.bindings().iter().for_each(|(name, value, mutable)| println!("{}", binding(name, value, *mutable, &scope.get_type(name)))),
+                Some("reset") => *scope = options.new_scope(),
+                Some("forget") if !arg.is_empty() => if !scope.forget(arg) {
+                    println!("{red}{} {std}", Exception::NotDefined(arg.to_owned()).format(&resources))
+                },
+                Some("?") if !arg.is_empty() => println!("{}", command_help(&resources.help, arg)),
+                _ => println!("{}", resources.help),
+            }
+            continue;
+        }
+        // a statement typed on several lines is a single entry
+        let _ = editor.add_history_entry(input);
+        let scope = &mut editor.helper_mut().unwrap().scope;
+        let expr = scope.read(input);
+        if expr.is_failure() {
+            println!("{red}{} {std}", expr.to_exception().format(&resources));
+            continue;
+        }
+        parse_warnings(input, &scope.enums()).iter().for_each(|w| println!("{blue}{}{std}", w.format(&resources)));
+        let result = interrupt::run_in_repl(|| expr.eval_or_failed(scope));
+        match (&expr, &result) {
+            (_, Expr::Failure(ex)) => println!("{red}{} {std}", ex.format(&resources)),
+            // the documentation returned by help is displayed as text
+            (Expr::Call(name, _), Expr::Str(doc)) if name == "help" => println!("{}", doc),
+            _ => println!("{}", result.print()),
+        }
+    }
+}
+
+fn load(_filename: &str) {
+    // TODO
+}
+
+fn save(_history: &DefaultHistory, _filename: &str) {
+    // TODO
+}
+
+// how to join the next line if the input is not a complete statement: a string, a bracket or a parenthesis
+// is not closed, or the input ends with an operator. Only blocks and collections accept new lines, otherwise
+// the lines are joined with a space and a comment at the end of the input is removed
+fn continuation(input: &str) -> Option<(usize, &'static str)> {
+    let mut open = Vec::new();
+    let (mut in_string, mut escaped, mut comment) = (false, false, None);
+    let mut last = ' ';
+    for (pos, c) in input.char_indices() {
+        match c {
+            '\n' => comment = None,
+            _ if comment.is_some() => continue,
+            '\\' if in_string => { escaped = !escaped; continue }
+            '"' if !escaped => in_string = !in_string,
+            _ if in_string => {}
+            '#' => comment = Some(pos),
+            '(' | '[' | '{' => open.push(c),
+            ')' | ']' | '}' => { open.pop(); }
+            _ => {}
+        }
+        escaped = false;
+        if comment.is_none() && !c.is_whitespace() {
+            last = c
+        }
+    }
+    let end = comment.unwrap_or(input.len());
+    if in_string {
+        Some((input.len(), "\n"))
+    } else if "+-*/%^=<>&|,.".contains(last) {
+        Some((end, " "))
+    } else {
+        open.last().map(|c| if_else!(*c == '(', (end, " "), (input.len(), "\n")))
+    }
+}
+
+fn find_command(abbrev: &str) -> Option<&'static str> {
+    COMMANDS.iter().find(|c| !abbrev.is_empty() && c.starts_with(abbrev)).copied()
+}
+
+// the help line of a command, or the complete help if the command is unknown
+fn command_help<'a>(help: &'a str, command: &str) -> &'a str {
+    find_command(command.trim_start_matches(':'))
+        .and_then(|c| help.lines().find(|l| l.starts_with(&format!(":{} ", c))))
+        .unwrap_or(help)
+}
+
+// a binding of the scope with its declared type, printed like its declaration
+fn binding(name: &str, value: &Expr, mutable: bool, declared: &Type) -> String {
+    match value {
+        // the definitions of an overloaded function have the same name
+        Expr::Fun(name, types, _) => format!("fun {}: {}", name, types.print()),
+        Expr::Struct(..) | Expr::Enum(..) | Expr::Interface(..) => type_declaration(value),
+        _ => format!("{} {}: {} = {}", if_else!(mutable, "var", "val"), name, declared.print(), value.print()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(Some("debug"), find_command("d"));
+        assert_eq!(Some("doc"), find_command("do"));
+        assert_eq!(None, find_command("x"));
+        let help = Resources::init("EN").help;
+        assert_eq!(":quit             quits the interpreter", command_help(&help, "q"));
+        assert_eq!(":doc <NAME>       displays the documentation of a function or a structure", command_help(&help, ":doc"));
+        assert_eq!(help, command_help(&help, "x"));
+    }
+
+    #[test]
+    fn test_continuation() {
+        assert_eq!(None, continuation("val a = 1"));
+        assert_eq!(None, continuation("fun inc(x: Int): Int = { x + 1 } # {"));
+        assert_eq!(Some((24, "\n")), continuation("fun inc(x: Int): Int = {"));
+        assert_eq!(Some((29, "\n")), continuation("fun inc(x: Int): Int = { # (x"));
+        assert_eq!(Some((17, " ")), continuation("val l = [1, [2], # ]"));
+        assert_eq!(Some((12, " ")), continuation("val a = 1 + # plus"));
+        assert_eq!(Some((8, " ")), continuation("print(a,"));
+        assert_eq!(Some((13, " ")), continuation("while (a < 10"));
+        assert_eq!(Some((14, "\n")), continuation("val s = \"a\\\" {"));
+        assert_eq!(None, continuation("val s = \"(\""));
+        assert_eq!(None, continuation("}"));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut scope = Options::default().new_scope();
+        scope.exec("var n = 1");
+        scope.exec("fun inc(x: Int): Int = x + 1");
+        scope.exec("struct Point(x: Float, y: Float)");
+        scope.exec("enum Color { Red, Green }");
+        let env: Vec<String> = scope.bindings().iter().map(|(name, value, mutable)| binding(name, value, *mutable, &scope.get_type(name))).collect();
+        assert_eq!(vec!("enum Color { Red, Green }", "struct Point(x: Float, y: Float)", "fun inc: (Int)->Int", "var n: Int = 1"), env);
+    }
+}
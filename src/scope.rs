@@ -1,24 +1,68 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-
-use dialoguer::Completion;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
 
 use crate::expr::Expr;
 use crate::expr::Expr::Fun;
 use crate::functions::add_functions;
+use crate::functions::Function::Defined;
 use crate::if_else;
 use crate::types::Type;
 
-#[derive(Debug, Clone)]
-pub struct Scope<'a> {
+#[derive(Default)]
+struct Frame {
     values: HashMap<String, Expr>,
     mutables: HashSet<String>,
-    parent: Option<&'a Scope<'a>>,
+    sandbox: bool,
+    // doc comments of the declared functions and structures
+    docs: HashMap<String, String>,
+    // types given in the declarations of the variables
+    types: HashMap<String, Type>,
+    // functions with several definitions
+    overloaded: HashSet<String>,
+    // the enums declaring each variant, which is not bound to its name
+    variants: HashMap<String, Vec<String>>,
+    // the enclosing local frame, the global frame is not part of the chain
+    parent: Option<Env>,
+}
+
+// a chain of local frames, shared by the scopes evaluated in it and by the functions defined in it which close over it
+#[derive(Clone, Default)]
+pub struct Env(Rc<RefCell<Frame>>);
+
+impl Env {
+    fn new(parent: Option<Env>) -> Env { Env(Rc::new(RefCell::new(Frame { parent, ..Frame::default() }))) }
+}
+
+impl PartialEq for Env {
+    fn eq(&self, other: &Self) -> bool { Rc::ptr_eq(&self.0, &other.0) }
 }
 
-impl Scope<'_> {
-    pub fn new<'a>(parent: Option<&'a Scope<'_>>) -> Scope<'a>  { Scope { values: HashMap::new(), mutables: HashSet::new(), parent }}
+impl Debug for Env {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { write!(f, "Env") }
+}
+
+// A scope is a local frame with its enclosing frames, and the global frame. A function is applied in a child of the
+// frames where it is defined, so that it sees their variables after they are gone from the scope of its caller.
+// The functions defined in the global frame do not capture it, and no closure references the global frame
+#[derive(Debug, Clone)]
+pub struct Scope {
+    local: Env,
+    // none for the global scope, whose frame is the local one
+    global: Option<Env>,
+}
+
+impl Scope {
+    pub fn new(parent: Option<&Scope>) -> Scope {
+        match parent {
+            None => Scope { local: Env::default(), global: None },
+            Some(p) => Scope { local: Env::new(p.global.as_ref().map(|_| p.local.clone())), global: Some(p.global().local) },
+        }
+    }
 
-    pub fn init<'a>() -> Scope<'a>  {
+    pub fn init() -> Scope {
         let mut scope = Scope::new(None);
         add_functions(&mut scope);
         scope
@@ -26,74 +70,233 @@ impl Scope<'_> {
     pub fn child(&self) -> Scope {
         Scope::new(Some(self))
     }
-    pub fn get(&self, name: &str) -> Option<&Expr> {
-        self.values.get(name)
+    // the scope of a call to a function closing over the frames of its definition
+    pub fn call(&self, closure: Option<&Env>) -> Scope {
+        Scope { local: Env::new(closure.cloned()), global: Some(self.global().local) }
     }
-    pub fn global(&self) -> &Scope {
-        self.parent.map(|s| s.global()).or(Some(self)).unwrap()
+    // the frames captured by a function defined in this scope, none in the global scope
+    pub fn closure(&self) -> Option<Env> {
+        self.global.as_ref().map(|_| self.local.clone())
     }
-    pub fn get_value(&self, name: &str) -> Option<Expr> {
-        self.get(name).map(|e| e.clone())
+    pub fn global(&self) -> Scope {
+        Scope { local: self.global.clone().unwrap_or_else(|| self.local.clone()), global: None }
     }
-    pub fn find(&self, name: &str) -> Option<&Expr> {
-        self.values.get(name).or(self.parent.map(|s| s.find(name)).flatten())
+    // the local frame and its enclosing ones, from the innermost, and the global frame
+    fn frames(&self) -> Vec<Scope> {
+        let mut frames = vec!(self.clone());
+        let mut parent = self.local.0.borrow().parent.clone();
+        while let Some(env) = parent {
+            parent = env.0.borrow().parent.clone();
+            frames.push(Scope { local: env, global: self.global.clone() });
+        }
+        frames.extend(self.global.as_ref().map(|_| self.global()));
+        frames
+    }
+    // a value of the local frame. A function defined in a local frame closes over it
+    pub fn get(&self, name: &str) -> Option<Expr> {
+        let frame = self.local.0.borrow();
+        frame.values.get(name).map(|value| match value {
+            Fun(name, types, Defined(params, body, None)) if self.global.is_some() =>
+                Fun(name.to_owned(), types.clone(), Defined(params.clone(), body.clone(), Some(self.local.clone()))),
+            _ => value.clone(),
+        })
+    }
+    pub fn find(&self, name: &str) -> Option<Expr> {
+        self.frames().iter().find_map(|s| s.get(name)).or_else(|| self.find_variant(name))
+    }
+    // a variant declared by a single enum, its value or the enum for a variant with fields
+    fn find_variant(&self, name: &str) -> Option<Expr> {
+        let [enum_name] = self.variant_enums(name).try_into().ok()?;
+        match self.frames().iter().find_map(|s| s.get(&enum_name))? {
+            Expr::Enum(_, variants) if variants.iter().any(|v| v.0 == name && v.1.is_empty()) =>
+                Some(Expr::Variant(Type::Enum(enum_name), name.to_owned(), vec!())),
+            definition => Some(definition),
+        }
+    }
+    // the enums declaring a variant, the name is ambiguous without its enum when there are several
+    pub fn variant_enums(&self, name: &str) -> Vec<String> {
+        let mut enums: Vec<String> = self.frames().iter().flat_map(|s| s.local.0.borrow().variants.get(name).cloned().unwrap_or_default()).collect();
+        enums.dedup();
+        enums
+    }
+    // the scope whose local frame declares a variable
+    pub fn declaring(&self, name: &str) -> Option<Scope> {
+        self.frames().into_iter().find(|s| s.is_defined(name, false))
     }
+    // a sandboxed scope has no access to the file system
+    pub fn set_sandbox(&mut self, sandbox: bool) { self.local.0.borrow_mut().sandbox = sandbox }
+    pub fn is_sandboxed(&self) -> bool { self.global().local.0.borrow().sandbox }
+
+    pub fn set_doc(&mut self, name: &str, doc: &str) { self.local.0.borrow_mut().docs.insert(name.to_owned(), doc.to_owned()); }
+    pub fn find_doc(&self, name: &str) -> Option<String> {
+        self.frames().iter().find_map(|s| s.local.0.borrow().docs.get(name).cloned())
+    }
+
     pub fn is_macro(&self, name: &str) -> bool {
         matches!(self.global().get(name), Some(Fun(_, Type::Macro, _)))
     }
 
-    pub fn add_fun(&mut self, value: Expr) {
-        match &value {
-            Fun(name, _, _) => self.values.insert(name.to_owned(), value),
+    // a function defined with other argument types than an existing one overloads it, and is stored with its
+    // argument types like 'area(Rect)'. Returns the key of the definition
+    pub fn add_fun(&mut self, value: Expr) -> String {
+        let mut frame = self.local.0.borrow_mut();
+        let key = match &value {
+            Fun(name, types, _) => match frame.values.get(name) {
+                Some(Fun(_, other, _)) if arguments(other) != arguments(types) => {
+                    frame.overloaded.insert(name.to_owned());
+                    overload_key(name, types)
+                }
+                _ => name.to_owned(),
+            },
             _ => panic!("cannot add {}", value)
         };
+        frame.values.insert(key.clone(), value);
+        key
+    }
+    // the definitions of a function with their keys, in the nearest scope defining it
+    pub fn find_overloads(&self, name: &str) -> Vec<(String, Expr)> {
+        self.frames().iter().map(|s| s.overloads(name)).find(|o| !o.is_empty()).unwrap_or_default()
     }
-    pub fn add_args(&mut self, vars: &Vec<String>, values: &Vec<Expr>) {
+    fn overloads(&self, name: &str) -> Vec<(String, Expr)> {
+        let mut keys = vec!(name.to_owned());
+        let frame = self.local.0.borrow();
+        if frame.overloaded.contains(name) {
+            let prefix = format!("{}(", name);
+            let mut others: Vec<String> = frame.values.keys().filter(|k| k.starts_with(&prefix)).cloned().collect();
+            others.sort();
+            keys.extend(others);
+        }
+        drop(frame);
+        keys.into_iter().filter_map(|k| self.get(&k).map(|f| (k, f))).collect()
+    }
+    // a function is already defined when one of its definitions has the same argument types, a macro cannot be overloaded
+    pub fn is_defined_fun(&self, name: &str, types: &Type, is_global: bool) -> bool {
+        let scope = if_else!(is_global, self.global(), self.clone());
+        match scope.get(name) {
+            Some(Fun(..)) => scope.find_overloads(name).iter().any(|(_, f)| {
+                let other = f.value_type();
+                arguments(&other).is_none() || arguments(types).is_none() || arguments(&other) == arguments(types)
+            }),
+            other => other.is_some(),
+        }
+    }
+    pub fn add_args(&mut self, vars: &[String], values: &[Expr]) {
+        let mut frame = self.local.0.borrow_mut();
         values.iter().zip(vars.iter()).for_each(|(v ,n)| {
-            self.values.insert(n.to_owned(), v.clone());
+            frame.values.insert(n.to_owned(), v.clone());
         });
     }
 
     pub fn is_defined(&self, name: &str, is_global: bool) -> bool {
-        if_else!(is_global, self.global().values.contains_key(name), self.values.contains_key(name))
+        let frame = self.global.as_ref().filter(|_| is_global).unwrap_or(&self.local);
+        frame.0.borrow().values.contains_key(name)
     }
     pub fn is_mutable(&self, name: &str) -> Option<bool> {
         if self.is_defined(name, false) {
-            Some(self.mutables.contains(name))
+            Some(self.local.0.borrow().mutables.contains(name))
         } else { None }
     }
-    pub fn get_type(&self, name: &str) -> &Type {
-        self.values.get(name).unwrap().get_type()
+    // the declared type of a variable, otherwise the type of its value
+    pub fn get_type(&self, name: &str) -> Type {
+        let frame = self.local.0.borrow();
+        frame.types.get(name).cloned().unwrap_or_else(|| frame.values.get(name).unwrap().value_type())
     }
+    pub fn set_type(&mut self, name: &str, declared: &Type) { self.local.0.borrow_mut().types.insert(name.to_owned(), declared.clone()); }
 
     pub fn set(&mut self, name: &str, value: Expr, is_mutable: Option<bool>) {
+        let mut frame = self.local.0.borrow_mut();
         if is_mutable == Some(true) {
-            self.mutables.insert(name.to_owned());
+            frame.mutables.insert(name.to_owned());
+        }
+        if let Expr::Enum(enum_name, variants) = &value {
+            variants.iter().filter(|_| enum_name == name).for_each(|v| frame.variants.entry(v.0.clone()).or_default().push(enum_name.clone()))
+        }
+        frame.values.insert(name.to_owned(), value);
+    }
+    // variables, functions, structures and enums declared by the user, sorted by name, with their mutability
+    pub fn bindings(&self) -> Vec<(String, Expr, bool)> {
+        let frame = self.local.0.borrow();
+        let mut bindings: Vec<(String, Expr, bool)> = frame.values.iter()
+            .filter(|(_, v)| !matches!(v, Fun(_, _, f) if !matches!(f, Defined(..))))
+            .map(|(n, v)| (n.clone(), v.clone(), frame.mutables.contains(n))).collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+    // the variants of the declared enums
+    pub fn enums(&self) -> Vec<Vec<String>> {
+        self.global().bindings().into_iter().filter_map(|b| match b.1 {
+            Expr::Enum(_, variants) => Some(variants.into_iter().map(|v| v.0).collect()),
+            _ => None,
+        }).collect()
+    }
+    // removes a declaration, returns false if it is not declared by the user
+    pub fn forget(&mut self, name: &str) -> bool {
+        if !self.bindings().iter().any(|b| b.0 == name) {
+            return false
+        }
+        let mut frame = self.local.0.borrow_mut();
+        if let Some(Expr::Enum(_, variants)) = frame.values.get(name) {
+            for variant in variants.clone() {
+                frame.variants.entry(variant.0).or_default().retain(|e| e != name)
+            }
         }
-        self.values.insert(name.to_owned(), value);
+        // the methods of an interface, with their overloads and docs
+        if let Some(Expr::Interface(..)) = frame.values.get(name) {
+            let prefix = format!("{}.", name);
+            frame.values.retain(|k, _| !k.starts_with(&prefix));
+            frame.overloaded.retain(|k| !k.starts_with(&prefix));
+            frame.docs.retain(|k, _| !k.starts_with(&prefix));
+        }
+        if frame.overloaded.remove(name) {
+            let prefix = format!("{}(", name);
+            frame.values.retain(|k, _| !k.starts_with(&prefix));
+        }
+        frame.values.remove(name);
+        frame.mutables.remove(name);
+        frame.docs.remove(name);
+        frame.types.remove(name);
+        true
     }
+
     pub fn read(&self, str: &str) -> Expr { Expr::read(str, self) }
 
     pub fn exec(&mut self, str: &str) -> String { self.read(str).eval_or_failed(self).print() }
 
-    pub fn find_fun(&self, prefix: &str) -> Option<String> {
-        if_else!(prefix.is_empty(), None, self.values.iter().find(|i| i.1.is_fun() && i.0.starts_with(prefix)).map(|i| i.0.clone()))
+    pub fn find_funs(&self, prefix: &str) -> Vec<String> {
+        let mut names: Vec<String> = self.local.0.borrow().values.iter().filter(|i| i.1.is_fun() && i.0.starts_with(prefix) && !i.0.contains('('))
+            .map(|i| i.0.clone()).collect();
+        names.sort();
+        names
     }
-}
 
-impl Completion for Scope<'_>  {
-    fn get(&self, input: &str) -> Option<String> {
+    // functions starting with the input, or methods when the input is an expression followed by '.name'
+    // the expression is never evaluated, only its type is inferred
+    pub fn completions(&self, input: &str) -> Vec<String> {
+        if input.is_empty() {
+            return vec!()
+        }
         let (expr, rest) = match input.rfind(".") {
             Some(p) => (&input[0..p], &input[p..]),
             None =>  (input, ""),
         };
-        match self.read(expr).eval(self) {
-            Ok(expr) => self.find_fun(&expr.get_type().method_name(rest)),
-            _ => self.find_fun(expr),
+        match self.read(expr).infer_type(self) {
+            Ok(Type::Fun(..)) | Err(_) => self.find_funs(expr),
+            Ok(t) => self.find_funs(&t.method_name(rest)),
         }
     }
 }
 
+fn arguments(types: &Type) -> Option<&Vec<Type>> {
+    match types {
+        Type::Fun(args, _) => Some(args),
+        _ => None,
+    }
+}
+
+fn overload_key(name: &str, types: &Type) -> String {
+    format!("{}({})", name, arguments(types).map(|args| args.iter().map(|t| t.print()).collect::<Vec<_>>().join(",")).unwrap_or_default())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::expr::Expr::Int;
@@ -106,19 +309,72 @@ mod tests {
         root.set("a", Int(1), None);
         root.set("b", Int(2), None);
 
-        assert_eq!(root.get("a"), Some(&Int(1)));
-        assert_eq!(root.global().get("b"), Some(&Int(2)));
+        assert_eq!(root.get("a"), Some(Int(1)));
+        assert_eq!(root.global().get("b"), Some(Int(2)));
 
         let mut child = root.child();
         child.set("c", Int(3), None);
         child.set("b", Int(4), None);
         assert_eq!(child.get("a"), None);
-        assert_eq!(child.find("a"), Some(&Int(1)));
-        assert_eq!(child.get("c"), Some(&Int(3)));
-        assert_eq!(child.get("b"), Some(&Int(4)));
-        assert_eq!(child.find("b"), Some(&Int(4)));
-        assert_eq!(root.global().get("b"), Some(&Int(2)));
+        assert_eq!(child.find("a"), Some(Int(1)));
+        assert_eq!(child.get("c"), Some(Int(3)));
+        assert_eq!(child.get("b"), Some(Int(4)));
+        assert_eq!(child.find("b"), Some(Int(4)));
+        assert_eq!(root.global().get("b"), Some(Int(2)));
+
+        let grandchild = child.child();
+        assert_eq!(grandchild.find("c"), Some(Int(3)));
+        assert_eq!(grandchild.declaring("b").and_then(|s| s.get("c")), Some(Int(3)));
+        assert_eq!(grandchild.global().get("b"), Some(Int(2)));
 
     }
 
+    #[test]
+    fn test_overloads() {
+        let mut scope = Scope::init();
+        scope.exec("fun f(x: Int): Int = 1");
+        scope.exec("fun f(x: Str): Int = 2");
+        let keys = |scope: &Scope| scope.find_overloads("f").iter().map(|f| f.0.to_owned()).collect::<Vec<_>>();
+        assert_eq!(vec!("f", "f(Str)"), keys(&scope));
+        assert_eq!(vec!("f", "fileExists", "fun"), scope.completions("f"));
+        let child = scope.child();
+        assert_eq!(vec!("f", "f(Str)"), keys(&child));
+        assert!(scope.forget("f"));
+        assert!(keys(&scope).is_empty());
+    }
+
+    #[test]
+    fn test_completions() {
+        let mut scope = Scope::init();
+        scope.exec("val s = \"abc\"");
+        assert_eq!(vec!("readFile", "readLine", "readLines"), scope.completions("rea"));
+        assert_eq!(vec!("Str.trim"), scope.completions("s.tr"));
+        assert_eq!(vec!("Str.read"), scope.completions("s.rea"));
+        assert!(scope.completions("zz").is_empty());
+        scope.exec("var n = 0");
+        scope.exec("fun bump(): Int = { n = n + 1 }");
+        assert_eq!(vec!("Int.toFloat"), scope.completions("bump().toF"));
+        assert_eq!("0", scope.exec("n"));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut scope = Scope::init();
+        scope.exec("var b = 1");
+        scope.exec("val a = \"x\"");
+        scope.exec("fun inc(x: Int): Int = x + 1");
+        let bindings: Vec<(String, bool)> = scope.bindings().iter().map(|b| (b.0.to_owned(), b.2)).collect();
+        assert_eq!(vec!(("a".to_owned(), false), ("b".to_owned(), true), ("inc".to_owned(), false)), bindings);
+        assert!(scope.forget("b"));
+        assert!(!scope.forget("b"));
+        assert!(!scope.forget("print"));
+        assert_eq!("NotDefined(b)", scope.exec("b = 2"));
+        assert_eq!("b", scope.exec("var b = 2.0"));
+        // the methods of a forgotten interface are removed with it
+        scope.exec("interface Shape { fun area(): Float }");
+        assert!(scope.forget("Shape"));
+        scope.exec("interface Shape { fun area(k: Int): Float }");
+        assert_eq!(vec!("Shape.area"), scope.find_overloads("Shape.area").iter().map(|f| f.0.to_owned()).collect::<Vec<_>>());
+    }
+
 }
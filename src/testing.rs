@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::exception::Exception;
+use crate::expr::Expr;
+use crate::format::find_files;
+use crate::parser::{parse_program, parse_warnings, SyntaxError};
+use crate::utils::{language, Resources};
+use crate::Options;
+
+#[derive(Debug)]
+struct TestResult {
+    name: String,
+    failure: Option<Exception>,
+    time: Duration,
+}
+
+#[derive(Debug)]
+struct TestFile {
+    path: String,
+    results: Vec<TestResult>,
+    warnings: Vec<SyntaxError>,
+}
+
+impl TestFile {
+    fn failures(&self) -> usize { self.results.iter().filter(|r| r.failure.is_some()).count() }
+    fn time(&self) -> Duration { self.results.iter().map(|r| r.time).sum() }
+}
+
+/// Runs the tests of the *_test.gro files, each one in a new scope created with the options, and writes a JUnit XML
+/// report if requested. Returns false if a test failed.
+pub fn run_tests(paths: &[String], junit: Option<&str>, options: &Options) -> bool {
+    let resources = Resources::init(&language());
+    let mut files = Vec::new();
+    paths.iter().for_each(|p| find_files(Path::new(p), "_test.gro", &mut files));
+    let reports: Vec<TestFile> = files.iter().map(|f| {
+        let report = run_file(f, options);
+        print!("{}", to_text(&report, &resources));
+        report
+    }).collect();
+    let failures: usize = reports.iter().map(|r| r.failures()).sum();
+    println!("{} tests, {} failures", reports.iter().map(|r| r.results.len()).sum::<usize>(), failures);
+    if let Some(path) = junit {
+        if let Err(e) = fs::write(path, to_junit(&reports, &resources)) {
+            eprintln!("{}: {}", path, e);
+            return false
+        }
+    }
+    failures == 0
+}
+
+fn run_file(path: &Path, options: &Options) -> TestFile {
+    let name = path.display().to_string();
+    match fs::read_to_string(path) {
+        Ok(source) => run_source(&name, &source, options),
+        Err(e) => TestFile { results: vec!(failed(&name, Exception::FileError(name.clone(), e.to_string()))), path: name, warnings: vec!() },
+    }
+}
+
+// the statements which are not tests are evaluated before each test
+fn run_source(name: &str, source: &str, options: &Options) -> TestFile {
+    let results = match parse_program(source) {
+        Ok(program) => {
+            let (tests, setup): (Vec<&Expr>, Vec<&Expr>) = program.iter().partition(|e| matches!(e, Expr::Call(n, _) if n == "test"));
+            tests.into_iter().map(|t| run_test(&setup, t, options)).collect()
+        }
+        Err(e) => vec!(failed(&format!("{}:{}:{}", name, e.line, e.column), Exception::CannotParse(e.message))),
+    };
+    TestFile { path: name.to_owned(), results, warnings: parse_warnings(source, &[]) }
+}
+
+fn run_test(setup: &[&Expr], test: &Expr, options: &Options) -> TestResult {
+    let name = match test {
+        Expr::Call(_, args) => args[0].to_str().unwrap_or_default().to_owned(),
+        _ => String::new(),
+    };
+    let start = Instant::now();
+    let mut scope = options.new_scope();
+    let result = setup.iter().chain([&test]).try_for_each(|e| e.eval_mutable(&mut scope).map(|_| ()));
+    TestResult { name, failure: result.err(), time: start.elapsed() }
+}
+
+fn failed(name: &str, ex: Exception) -> TestResult {
+    TestResult { name: name.to_owned(), failure: Some(ex), time: Duration::ZERO }
+}
+
+fn to_text(file: &TestFile, resources: &Resources) -> String {
+    let mut text = format!("{}\n", file.path);
+    for warning in &file.warnings {
+        text += &format!("  WARN  {}:{}: {}\n", warning.line, warning.column, warning.format(resources))
+    }
+    for result in &file.results {
+        match &result.failure {
+            None => text += &format!("  ok    {}\n", result.name),
+            Some(ex) => {
+                text += &format!("  FAIL  {}: {}\n", result.name, ex.format(resources));
+                if let Exception::NotEqual(expected, actual) = ex {
+                    diff(expected, actual).lines().for_each(|l| text += &format!("        {}\n", l))
+                }
+            }
+        }
+    }
+    text
+}
+
+// printed values, with a mark under the first difference
+fn diff(expected: &str, actual: &str) -> String {
+    let same = expected.chars().zip(actual.chars()).take_while(|(e, a)| e == a).count();
+    format!("- {}\n+ {}\n  {}^", expected, actual, " ".repeat(same))
+}
+
+fn to_junit(files: &[TestFile], resources: &Resources) -> String {
+    let tests: usize = files.iter().map(|f| f.results.len()).sum();
+    let failures: usize = files.iter().map(|f| f.failures()).sum();
+    let mut xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n".to_owned();
+    xml += &format!("<testsuites tests=\"{}\" failures=\"{}\">\n", tests, failures);
+    for file in files {
+        xml += &format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+                        escape(&file.path), file.results.len(), file.failures(), file.time().as_secs_f64());
+        for result in &file.results {
+            let testcase = format!("    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"", escape(&result.name), escape(&file.path), result.time.as_secs_f64());
+            xml += &match &result.failure {
+                None => format!("{}/>\n", testcase),
+                Some(ex) => {
+                    let message = ex.format(resources);
+                    let details = match ex {
+                        Exception::NotEqual(expected, actual) => diff(expected, actual),
+                        _ => message.clone(),
+                    };
+                    format!("{}>\n      <failure message=\"{}\" type=\"{}\">{}</failure>\n    </testcase>\n", testcase, escape(&message), ex.name(), escape(&details))
+                }
+            }
+        }
+        xml += "  </testsuite>\n";
+    }
+    xml + "</testsuites>\n"
+}
+
+fn escape(str: &str) -> String {
+    str.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    const SOURCE: &str = r#"
+fun inc(x: Int): Int = x + 1
+test "inc" {
+  assertEquals(2, inc(1))
+}
+test "list" {
+  assertEquals([1,2], [1,3])
+}
+test "assert" {
+  assert(inc(0) > 1, "not <1>")
+}
+"#;
+
+    #[test]
+    fn test_run() {
+        let file = run_source("inc_test.gro", SOURCE, &Options::default());
+        assert_eq!(vec!("inc", "list", "assert"), file.results.iter().map(|r| r.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(vec!(None, Some(Exception::NotEqual("[1,2]".to_owned(), "[1,3]".to_owned())), Some(Exception::AssertionFailed("not <1>".to_owned()))),
+                   file.results.into_iter().map(|r| r.failure).collect::<Vec<_>>());
+        let bad = run_source("bad_test.gro", "val a =", &Options::default());
+        assert_eq!(("bad_test.gro:1:8", &Some(Exception::CannotParse("ExpectedValue =".to_owned()))), (bad.results[0].name.as_str(), &bad.results[0].failure));
+        assert_eq!("- [1,2]\n+ [1,3]\n     ^", diff("[1,2]", "[1,3]"));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let resources = Resources::init("EN");
+        let file = run_source("color_test.gro", "enum Color { Red, Green, Blue }\nfun f(c: Color): Int = match (c) {\n  Red -> 1\n  Green -> 2\n}", &Options::default());
+        assert_eq!("color_test.gro\n  WARN  2:24: the match does not handle the variants Blue, add them or an `else` case\n", to_text(&file, &resources));
+    }
+
+    #[test]
+    fn test_isolation() {
+        let file = run_source("isolation_test.gro", "var n = 0\ntest \"a\" { n = n + 1 ; assertEquals(1, n) }\ntest \"b\" { n = n + 1 ; assertEquals(1, n) }", &Options::default());
+        assert_eq!(0, file.failures());
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let source = "test \"sandbox\" { assert(listDir(\".\").isFailure(), \"files\") }";
+        assert_eq!(0, run_source("sandbox_test.gro", source, &Options { sandbox: true, ..Options::default() }).failures());
+        assert_eq!(1, run_source("sandbox_test.gro", source, &Options::default()).failures());
+    }
+
+    #[test]
+    fn test_equality() {
+        let file = run_source("equality_test.gro", "test \"map\" { assertEquals({\"a\": 1, \"b\": 2}, {\"b\": 2, \"a\": 1}) }\ntest \"number\" { assertEquals(1, 1.0) }", &Options::default());
+        assert_eq!(0, file.failures(), "{:?}", file);
+    }
+
+    #[test]
+    fn test_junit() {
+        let resources = Resources::init("EN");
+        let xml = to_junit(&[run_source("inc_test.gro", SOURCE, &Options::default())], &resources);
+        assert!(xml.contains("<testsuites tests=\"3\" failures=\"2\">"));
+        assert!(xml.contains("<testcase name=\"inc\" classname=\"inc_test.gro\""));
+        assert!(xml.contains("<failure message=\"Expected [1,2] but got [1,3]\" type=\"NotEqual\">- [1,2]\n+ [1,3]\n     ^</failure>"));
+        assert!(xml.contains("<failure message=\"Assertion failed: not &lt;1&gt;\" type=\"AssertionFailed\">"));
+    }
+
+    #[test]
+    fn test_samples() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("samples");
+        let mut files = Vec::new();
+        find_files(&dir, "_test.gro", &mut files);
+        assert!(!files.is_empty());
+        files.iter().map(|f| run_file(f, &Options::default())).for_each(|f| assert_eq!(0, f.failures(), "{:?}", f));
+    }
+}
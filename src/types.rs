@@ -1,4 +1,6 @@
 use std::borrow::ToOwned;
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::string::ToString;
 
 use strum_macros::Display;
@@ -6,9 +8,11 @@ use strum_macros::Display;
 use crate::exception::Exception;
 use crate::expr::Expr;
 use crate::if_else;
+use crate::parser::parse_type;
+use crate::scope::Scope;
 use crate::types::Type::_Undefined;
 
-use self::Type::{Any, Bool, Float, Fun, Int, List, Map, Option, Str, Try, Struct, Macro, Number};
+use self::Type::{Any, Enum, Float, Fun, Int, List, Map, Option, Try, Struct, Number, Param, Tuple};
 
 #[derive(Debug, Eq, PartialEq, Clone, Display)]
 pub enum Type {
@@ -25,44 +29,23 @@ pub enum Type {
     Map(Box<Type>, Box<Type>),
     Fun(Vec<Type>, Box<Type>),
     Macro,
-    Struct(String)
+    // a structure with its type arguments
+    Struct(String, Vec<Type>),
+    // type parameter of a generic function or structure
+    Param(String),
+    Tuple(Vec<Type>),
+    Enum(String),
 }
 
-impl Type {
-    pub fn from_str(str: &str) -> Result<Type, Exception> {
-        if str.starts_with(":") {
-            Type::from_str(&str[1..])
-        } else if str.starts_with("(") {
-            let args: Vec<&str>  = str[1..str.len()].split(")->").collect();
-            args[0].split(",").map(Type::from_str).collect::<Result<Vec<_>, _> >().and_then(
-                |vec| Type::from_str(args[1]).map(|o| Fun(vec, Box::new(o)))
-            )
-        } else if str.ends_with("?") {
-            Type::from_str(&str[0..str.len() - 1]).map(|t| Option(Box::new(t)))
-        } else if str.ends_with("!") {
-            Type::from_str(&str[0..str.len() - 1]).map(|t| Try(Box::new(t)))
-        } else if str.starts_with("List<") {
-            Type::from_str(&str[5..str.len() - 1]).map(|t| List(Box::new(t)))
-        } else if str.starts_with("Map<") {
-            let args: Vec<&str> = (&str[4..str.len() - 1]).split(',').collect();
-            if args.len() == 2 {
-                args.into_iter().map(Type::from_str).collect::<Result<Vec<_>, _>>().and_then(
-                    |vec| Ok(Map(Box::new(vec[0].clone()), Box::new(vec[1].clone())))
-                )
-            } else {  Err(Exception::CannotParse("Map type".to_owned())) }
-        } else {
-            match str {
-                "Any" => Ok(Any),
-                "Int" => Ok(Int),
-                "Bool" => Ok(Bool),
-                "Str" => Ok(Str),
-                "Float" => Ok(Float),
-                "Number" => Ok(Number),
-                "Macro" => Ok(Macro),
-                _ => if_else!(str.chars().all(|c| c.is_alphabetic()), Ok(Struct(str.to_string())), Err(Exception::CannotParse(str.to_owned())))
-            }
-        }
+impl FromStr for Type {
+    type Err = Exception;
+
+    fn from_str(str: &str) -> Result<Type, Exception> {
+        parse_type(str.strip_prefix(':').unwrap_or(str)).map_err(|e| Exception::CannotParse(e.message))
     }
+}
+
+impl Type {
     // same derived from strum
     pub fn name(&self) -> String { self.to_string() }
 
@@ -72,47 +55,171 @@ impl Type {
 
     pub fn is_defined(&self) -> bool { *self != _Undefined }
 
-    pub fn matches(&self, expected: &Type) -> bool {
-        *expected == Any || *self == *expected || (*expected == Number && self.is_number())
+    // Int and Float are Numbers, and every type is Any. Collections, options, structures and tuples are immutable
+    // so they are covariant, functions are contravariant in their arguments. A value is also an Option or a Try
+    // of its type, and nil, which has the type Any, is an Option of any type
+    pub fn is_subtype_of(&self, expected: &Type, scope: &Scope) -> bool {
+        match (self, expected) {
+            _ if self == expected => true,
+            (_, Any) => true,
+            // type parameters are only checked when a generic function is called
+            (_, Param(_)) => true,
+            (Int | Float, Number) => true,
+            (List(t), List(u)) | (Option(t), Option(u)) | (Try(t), Try(u)) => t.is_subtype_of(u, scope),
+            (Map(k, v), Map(l, w)) => k.is_subtype_of(l, scope) && v.is_subtype_of(w, scope),
+            // the name of an enum in a declaration is parsed as a structure
+            (Enum(n), Struct(m, args)) => n == m && args.is_empty(),
+            (Struct(..), Struct(m, others)) if others.is_empty() && self.interfaces(scope).contains(m) => true,
+            (Struct(n, args), Struct(m, others)) if n == m && args.len() == others.len() => args.iter().zip(others).all(|(t, u)| t.is_subtype_of(u, scope)),
+            (Tuple(args), Tuple(others)) if args.len() == others.len() => args.iter().zip(others).all(|(t, u)| t.is_subtype_of(u, scope)),
+            (Fun(args, o), Fun(others, p)) if args.len() == others.len() =>
+                others.iter().zip(args).all(|(t, u)| t.is_subtype_of(u, scope)) && o.is_subtype_of(p, scope),
+            (Any, Option(_)) => true,
+            (_, Option(t)) | (_, Try(t)) => self.is_subtype_of(t, scope),
+            _ => false,
+        }
+    }
+
+    pub fn is_generic(&self) -> bool {
+        match self {
+            Param(_) => true,
+            List(t) | Option(t) | Try(t) => t.is_generic(),
+            Map(k, v) => k.is_generic() || v.is_generic(),
+            Struct(_, args) | Tuple(args) => args.iter().any(|t| t.is_generic()),
+            Fun(args, output) => output.is_generic() || args.iter().any(|t| t.is_generic()),
+            _ => false,
+        }
+    }
+
+    // checks that an actual type matches an expected one, binding the type parameters to the actual types.
+    // A parameter bound to Int and Float is bound to Number
+    pub fn unify(&self, actual: &Type, bindings: &mut HashMap<String, Type>, scope: &Scope) -> bool {
+        match (self, actual) {
+            (Param(name), _) => match bindings.get(name) {
+                Some(bound) if actual.is_subtype_of(bound, scope) => true,
+                Some(bound) if bound.is_number() && actual.is_number() => { bindings.insert(name.to_owned(), Number); true }
+                Some(_) => false,
+                None => { bindings.insert(name.to_owned(), actual.clone()); true }
+            },
+            // the elements of an empty or mixed collection are not checked
+            (List(_), List(a)) | (Map(_, _), Map(_, a)) if **a == Any => true,
+            (List(e), List(a)) | (Option(e), Option(a)) => e.unify(a, bindings, scope),
+            (Map(ek, ev), Map(ak, av)) => ek.unify(ak, bindings, scope) && ev.unify(av, bindings, scope),
+            (Struct(e, eargs), Struct(a, aargs)) if e == a && eargs.len() == aargs.len() => eargs.iter().zip(aargs).all(|(e, a)| e.unify(a, bindings, scope)),
+            (Tuple(eargs), Tuple(aargs)) if eargs.len() == aargs.len() => eargs.iter().zip(aargs).all(|(e, a)| e.unify(a, bindings, scope)),
+            (Fun(eargs, eo), Fun(aargs, ao)) if eargs.len() == aargs.len() => eargs.iter().zip(aargs).all(|(e, a)| e.unify(a, bindings, scope)) && eo.unify(ao, bindings, scope),
+            (Option(_), Any) => true,
+            (Try(e), _) | (Option(e), _) => e.unify(actual, bindings, scope),
+            _ => actual.is_subtype_of(self, scope),
+        }
     }
 
-    pub fn infer_list(vec: &Vec<Expr>) -> Type {
-        List(Box::new(infer(vec).clone()))
+    // replaces the type parameters by their bound types, Any when they are not bound
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        self.map(&|t| match t {
+            Param(name) => Some(bindings.get(name).cloned().unwrap_or(Any)),
+            _ => None,
+        })
     }
-    pub fn infer_map(vec: &Vec<(Expr, Expr)>) -> Type {
-        Map(Box::new(infer(&vec.iter().map(|p| p.0.clone()).collect::<Vec<_>>()).clone()), Box::new(infer(&vec.iter().map(|p| p.1.clone()).collect::<Vec<_>>()).clone()))
+
+    // in the declaration of a generic function or structure, the type parameters are parsed as structures
+    pub fn with_params(&self, params: &[String]) -> Type {
+        self.map(&|t| match t {
+            Struct(name, args) if args.is_empty() && params.contains(name) => Some(Param(name.to_owned())),
+            _ => None,
+        })
+    }
+
+    // replaces the types for which the function returns a new type
+    fn map(&self, f: &dyn Fn(&Type) -> std::option::Option<Type>) -> Type {
+        if let Some(t) = f(self) {
+            return t
+        }
+        match self {
+            List(t) => List(Box::new(t.map(f))),
+            Option(t) => Option(Box::new(t.map(f))),
+            Try(t) => Try(Box::new(t.map(f))),
+            Map(k, v) => Map(Box::new(k.map(f)), Box::new(v.map(f))),
+            Struct(name, args) => Struct(name.to_owned(), args.iter().map(|t| t.map(f)).collect()),
+            Tuple(args) => Tuple(args.iter().map(|t| t.map(f)).collect()),
+            Fun(args, output) => Fun(args.iter().map(|t| t.map(f)).collect(), Box::new(output.map(f))),
+            _ => self.clone(),
+        }
+    }
+
+    // the type of the value of an Option or a Try
+    pub fn without_option(&self) -> &Type {
+        match self {
+            Option(t) | Try(t) => t.without_option(),
+            _ => self,
+        }
+    }
+
+    pub fn accepts_failure(&self) -> bool {
+        matches!(self, Any | Try(_))
+    }
+
+    pub fn infer_list(vec: &[Expr]) -> Type {
+        List(Box::new(Type::common(vec.iter().map(Expr::value_type))))
+    }
+    pub fn infer_map(vec: &[(Expr, Expr)]) -> Type {
+        Map(Box::new(Type::common(vec.iter().map(|p| p.0.value_type()))), Box::new(Type::common(vec.iter().map(|p| p.1.value_type()))))
+    }
+    // the type shared by the elements of a collection
+    pub fn common(mut types: impl Iterator<Item = Type>) -> Type {
+        let mut current = types.next().unwrap_or(Any);
+        for other in types {
+            if current != other && current != Any {
+                current = if_else!(current.is_number() && other.is_number(), Number, Any)
+            }
+        }
+        current
     }
 
     pub fn print(&self) -> String {
         match self {
             List(t) => format!("List<{}>", t.print()),
             Map(t, u) => format!("Map<{},{}>", t.print() , u.print()),
+            Struct(name, args) if args.is_empty() => name.to_owned(),
+            Enum(name) => name.to_owned(),
+            Struct(name, args) => format!("{}<{}>", name, args.iter().map(|t| t.print()).collect::<Vec<_>>().join(",")),
+            Param(name) => name.to_owned(),
+            Tuple(args) => format!("({})", args.iter().map(|t| t.print()).collect::<Vec<_>>().join(",")),
+            Option(t) => format!("{}?", t.print()),
+            Try(t) => format!("{}!", t.print()),
+            Fun(args, output) => format!("({})->{}", args.iter().map(|t| t.print()).collect::<Vec<_>>().join(","), output.print()),
             _ => self.name()
         }
     }
+    // the interfaces implemented by a structure, declared with 'struct Circle(r: Float) : Shape'
+    pub fn interfaces(&self, scope: &Scope) -> Vec<String> {
+        match self {
+            Struct(name, _) => match scope.find(name) {
+                Some(Expr::Struct(_, _, _, interfaces)) => interfaces,
+                _ => vec!(),
+            },
+            _ => vec!(),
+        }
+    }
+
+    // the methods of a structure or an enum are prefixed by its name, those of the other types by the name of the type
     pub fn method_name(&self, name: &str) -> String {
-        self.name().to_owned() + if_else!(name.starts_with("."), "", ".") + name
+        let prefix = match self {
+            Struct(name, _) | Enum(name) => name.to_owned(),
+            _ => self.name(),
+        };
+        prefix + if_else!(name.starts_with("."), "", ".") + name
     }
-    pub fn all_method_names(&self, name: &str) -> Vec<String> {
+    pub fn all_method_names(&self, name: &str, scope: &Scope) -> Vec<String> {
         let mut vec = vec!(self.method_name(name));
-        if self.is_number() {
+        vec.extend(self.interfaces(scope).iter().map(|i| Struct(i.to_owned(), vec!()).method_name(name)));
+        if self.is_number() && *self != Number {
             vec.push(Number.method_name(name));
         }
-        vec.push(Any.method_name(name));
-        vec
-    }
-}
-
-fn infer(vec: &Vec<Expr>) -> &Type {
-    if vec.is_empty() { &Any } else {
-        let mut current = vec[0].get_type();
-        for e in vec[1..].iter() {
-            let other = e.get_type();
-            if *current != *other && *current != Any {
-                current = if_else!(current.is_number() && other.is_number(), &Number, &Any)
-            }
+        if *self != Any {
+            vec.push(Any.method_name(name));
         }
-        current
+        vec
     }
 }
 
@@ -120,6 +227,7 @@ fn infer(vec: &Vec<Expr>) -> &Type {
 mod tests {
     use crate::expr::TRUE;
     use super::*;
+    use super::Type::{Bool, Str};
     fn read(str: &str) -> Type { Type::from_str(str).unwrap() }
 
     #[test]
@@ -132,8 +240,16 @@ mod tests {
         assert_eq!(Option(Box::new(Int)), read("Int?"));
         assert_eq!(Try(Box::new(Int)), read("Int!"));
         assert_eq!(Fun(vec!(Int, Float), Box::new(Float)), read("(Int,Float)->Float"));
-        assert_eq!(Struct("Point".to_owned()), read("Point"));
-        assert_eq!(Err(Exception::CannotParse("Poi!nt".to_string())), Type::from_str("Poi!nt"));
+        assert_eq!(Fun(vec!(), Box::new(Any)), read("()->Any"));
+        assert_eq!(Struct("Point".to_owned(), vec!()), read("Point"));
+        assert_eq!(Struct("Pair".to_owned(), vec!(Int, Struct("B".to_owned(), vec!()))), read("Pair<Int, B>"));
+        assert_eq!(Map(Box::new(Str), Box::new(List(Box::new(Option(Box::new(Int)))))), read("Map<Str, List<Int?>>"));
+        assert_eq!(Fun(vec!(Int, Int), Box::new(Bool)), read("(Int, Int) -> Bool"));
+        assert_eq!(Option(Box::new(Fun(vec!(Int), Box::new(Int)))), read("((Int) -> Int)?"));
+        assert_eq!(Fun(vec!(Fun(vec!(Any), Box::new(Bool))), Box::new(Try(Box::new(Tuple(vec!(Int, Str)))))), read("((Any)->Bool)->(Int,Str)!"));
+        assert_eq!(Err(Exception::CannotParse("UnexpectedToken nt".to_string())), Type::from_str("Poi!nt"));
+        assert_eq!(Err(Exception::CannotParse("TypeArguments Map".to_string())), Type::from_str("Map<Int>"));
+        assert_eq!(Err(Exception::CannotParse("UnknownType int".to_string())), Type::from_str("List<int>"));
     }
 
     #[test]
@@ -141,13 +257,46 @@ mod tests {
         let t = List(Box::new(Int));
         assert_eq!("List<Int>", t.print());
         assert_eq!("List", t.to_string());
+        assert_eq!("(Int,Str)?", read("(Int, Str)?").print());
+    }
+
+    #[test]
+    fn test_subtyping() {
+        let scope = Scope::init();
+        let sub = |t: &str, u: &str| read(t).is_subtype_of(&read(u), &scope);
+        assert!(sub("Int", "Number") && sub("Number", "Any") && sub("Float", "Any"));
+        assert!(!sub("Number", "Int") && !sub("Str", "Number"));
+        assert!(sub("List<Int>", "List<Number>") && sub("Map<Str, List<Float>>", "Map<Str, List<Number>>"));
+        assert!(!sub("List<Number>", "List<Int>") && !sub("List<Int>", "Map<Int, Int>"));
+        assert!(sub("Int", "Int?") && sub("Int?", "Number?") && sub("Any", "Int?") && !sub("Int?", "Int"));
+        assert!(sub("Int", "Number!") && sub("Int!", "Number!") && !sub("Int!", "Int"));
+        assert!(sub("(Number) -> Int", "(Int) -> Number") && !sub("(Int) -> Int", "(Number) -> Int"));
+        assert!(sub("(Int, Str)", "(Number, Any)") && !sub("(Int, Str)", "(Int, Str, Str)"));
+        assert!(sub("Pair<Int, Str>", "Pair<Number, Str>") && !sub("Pair<Int>", "Point<Int>"));
+    }
+
+    #[test]
+    fn test_generics() {
+        let params = vec!("T".to_owned(), "U".to_owned());
+        let scope = Scope::init();
+        let t = read("Map<T,List<U>>").with_params(&params);
+        assert!(t.is_generic());
+        let mut bindings = HashMap::new();
+        assert!(t.unify(&read("Map<Str,List<Int>>"), &mut bindings, &scope));
+        assert_eq!("Pair<Str,Int>", read("Pair<T,U>").with_params(&params).substitute(&bindings).print());
+        let t = Param("T".to_owned());
+        let mut bindings = HashMap::new();
+        assert!(t.unify(&Int, &mut bindings, &scope) && t.unify(&Float, &mut bindings, &scope));
+        assert_eq!(Some(&Number), bindings.get("T"));
+        assert!(!t.unify(&Str, &mut bindings, &scope));
+        assert_eq!(Option(Box::new(Any)), read("U?").with_params(&params).substitute(&bindings));
     }
 
     #[test]
     fn test_infer() {
-        assert_eq!(&Any, infer(&vec!()));
-        assert_eq!(&Int, infer(&vec!(Expr::Int(1), Expr::Int(2))));
-        assert_eq!(&Number, infer(&vec!(Expr::Int(1), Expr::Float(2.0))));
-        assert_eq!(&Any, infer(&vec!(Expr::Int(1), TRUE)));
+        assert_eq!(List(Box::new(Any)), Type::infer_list(&[]));
+        assert_eq!(List(Box::new(Int)), Type::infer_list(&[Expr::Int(1), Expr::Int(2)]));
+        assert_eq!(List(Box::new(Number)), Type::infer_list(&[Expr::Int(1), Expr::Float(2.0)]));
+        assert_eq!(List(Box::new(Any)), Type::infer_list(&[Expr::Int(1), TRUE]));
     }
 }
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use std::str::from_utf8;
 use rust_embed::Embed;
 
+use crate::if_else;
+
 #[derive(Embed)]
 #[folder = "resources/"]
 struct Asset;
@@ -9,24 +11,50 @@ struct Asset;
 #[derive(Debug)]
 pub struct Resources {
     pub help: String,
-    messages: HashMap<String, String>
+    messages: HashMap<String, String>,
+    docs: HashMap<String, String>,
 }
 
 impl Resources {
-    pub fn new(help: String, messages: HashMap<String, String>) -> Resources { Resources { help, messages }}
+    pub fn new(help: String, messages: HashMap<String, String>, docs: HashMap<String, String>) -> Resources { Resources { help, messages, docs }}
 
+    // the messages are in English for the languages without resource files
     pub fn init(lang: &str) -> Resources {
-        Resources::new(read_resource(lang, "help"), to_map(read_resource(lang, "msg")))
+        let lang = if_else!(Asset::get(&format!("help_{}.txt", lang)).is_some(), lang, "EN");
+        Resources::new(read_resource(lang, "help"), to_map(read_resource(lang, "msg")), to_map(read_resource(lang, "doc")))
     }
     pub fn get(&self, name: &str) -> Option<&String> {
         self.messages.get(name)
     }
+    // message of a syntax error code like 'MissingClosing )', or the code itself when it is unknown
+    pub fn get_syntax(&self, code: &str) -> String {
+        let (key, arg) = code.split_once(' ').unwrap_or((code, ""));
+        self.get(key).map(|m| m.replace("{1}", arg)).unwrap_or_else(|| code.to_owned())
+    }
+    // description of a builtin function
+    pub fn get_doc(&self, name: &str) -> Option<&String> {
+        self.docs.get(name)
+    }
+}
+
+// language of the messages, from the system locale when available
+pub fn language() -> String {
+    #[cfg(feature = "repl")]
+    let locale = sys_locale::get_locale().unwrap_or_else(|| String::from("FR"));
+    #[cfg(not(feature = "repl"))]
+    let locale = String::from("EN");
+    language_code(&locale)
+}
+
+// the 2 first letters of a locale like 'fr-FR'
+fn language_code(locale: &str) -> String {
+    locale.chars().take(2).collect::<String>().to_uppercase()
 }
 
 fn read_resource(lang: &str, name: &str) -> String {
-    let asset = Asset::get(&format!("{}_{}.txt", name, lang)).expect(&format!("No help file for language {}", lang));
+    let asset = Asset::get(&format!("{}_{}.txt", name, lang)).unwrap_or_else(|| panic!("No help file for language {}", lang));
     let str = from_utf8(asset.data.as_ref()).expect("Invalid resource file");
-    return str.to_owned();
+    str.to_owned()
 }
 
 fn to_map(str: String) -> HashMap<String, String> {
@@ -37,12 +65,22 @@ fn to_map(str: String) -> HashMap<String, String> {
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::Resources;
+    use crate::utils::{language_code, Resources};
 
     #[test]
     fn test_get() {
         let resources = Resources::init("FR");
-        assert_eq!(6, resources.messages.len());
-        assert_eq!("Le symbole '{1}' n'est pas défini", resources.get("UndefinedSymbol").unwrap())
+        assert_eq!(42, resources.messages.len());
+        assert!(resources.messages.keys().all(|key| !key.contains(' ')));
+        assert_eq!("Le symbole '{1}' n'est pas défini", resources.get("UndefinedSymbol").unwrap());
+        assert_eq!("Supprime les espaces au début et à la fin de la chaine", resources.get_doc("Str.trim").unwrap())
+    }
+
+    #[test]
+    fn test_language() {
+        assert_eq!("FR", language_code("fr-FR"));
+        assert_eq!("C", language_code("C"));
+        assert_eq!("ÉS", language_code("és"));
+        assert_eq!("The symbol '{1}' is not defined", Resources::init("DE").get("UndefinedSymbol").unwrap());
     }
 }